@@ -1,20 +1,118 @@
+use crate::genetics::GeneEncoding;
+use crate::island::Topology;
+use crate::novelty::{Behaviour, EvaluationMode};
+use crate::population::SelectionMethod;
+use crate::species::DistanceMetric;
 use nannou::prelude::*;
+use std::time::Duration;
 
 // Window Configs
 pub const SCREEN_DIMENSIONS: u32 = 720;
 
 // Simulation Configs
 pub const NUM_ROCKETS: u32 = 750;
+/// Seed for all randomness in the evolution, a random seed is used if `None`
+/// Runs with the same seed and configs produce identical results
+pub const SIMULATION_SEED: Option<u64> = None;
 pub const SIM_BACKGROUND: Rgb<u8> = DARKSLATEGREY;
 
+// Stopping Criteria Configs
+/// Criteria ending headless runs (`--headless`), `None` disables a criterion
+pub const MAX_GENERATIONS: Option<u32> = Some(1000);
+/// Fraction of rockets reaching the target in a generation
+pub const TARGET_SUCCESS_RATE: Option<f32> = Some(0.9);
+/// Generations in a row without the best fitness improving by more than `PLATEAU_TOLERANCE`
+pub const PLATEAU_GENERATIONS: Option<u32> = Some(100);
+/// Relative improvement, e.g 0.01 is 1%
+pub const PLATEAU_TOLERANCE: f32 = 0.01;
+pub const TIME_BUDGET: Option<Duration> = None;
+
+// Sweep Configs
+/// Runs of every combination of parameters in a sweep (`--sweep`)
+pub const SWEEP_SEEDS: usize = 5;
+pub const SWEEP_CSV_PATH: &str = "sweep.csv";
+
+// Island Configs
+/// Number of sub-populations the `NUM_ROCKETS` rockets are split into,
+/// 1 disables the island model
+pub const NUM_ISLANDS: usize = 1;
+/// Islands exchange migrants every `MIGRATION_INTERVAL` generations
+pub const MIGRATION_INTERVAL: u32 = 10;
+/// Number of fittest rockets each island sends to every destination island
+pub const MIGRANT_COUNT: usize = 10;
+pub const MIGRATION_TOPOLOGY: Topology = Topology::Ring;
+
+// Selection Configs
+pub const SELECTION_METHOD: SelectionMethod = SelectionMethod::FitnessProportionate;
+/// The Pareto front is exported here when using `SelectionMethod::Nsga2`
+pub const PARETO_CSV_PATH: &str = "pareto_front.csv";
+
+// Speciation Configs
+/// Share the fitness of every rocket among the members of its species,
+/// keeps less crowded routes alive
+pub const FITNESS_SHARING: bool = false;
+pub const SPECIES_DISTANCE_METRIC: DistanceMetric = DistanceMetric::Trajectory;
+/// Rockets closer than this to a species representative belong to that species,
+/// in gene units for `DistanceMetric::Genes` and pixels for `DistanceMetric::Trajectory`
+pub const SPECIES_DISTANCE_THRESHOLD: f32 = 60.0;
+
+// Novelty Search Configs
+/// How rockets are scored at the end of every generation
+pub const EVALUATION_MODE: EvaluationMode = EvaluationMode::Objective;
+pub const NOVELTY_BEHAVIOUR: Behaviour = Behaviour::EndPosition;
+/// Number of nearest behaviours novelty is measured against
+pub const NOVELTY_NEAREST_NEIGHBOURS: usize = 15;
+/// Behaviours with a novelty (in pixels) above this are added to the archive
+pub const NOVELTY_ARCHIVE_THRESHOLD: f32 = 40.0;
+/// Maximum number of archived behaviours, the oldest are dropped first
+pub const NOVELTY_ARCHIVE_SIZE: usize = 1000;
+
 // Target Configs
 pub const TARGET_LOCATION: (f32, f32) = (330.0, 0.0);
 pub const TARGET_COLOR: Rgb<u8> = GOLD;
 pub const TARGET_RADIUS: f32 = 22.0;
+/// Checkpoints of a course, passed in order before the target
+pub const CHECKPOINT_COLOR: Rgb<u8> = KHAKI;
+/// Fitness added for every checkpoint passed,
+/// larger than the fitness a rocket can get from its distance alone
+pub const CHECKPOINT_BONUS: f32 = 2.0 / (TARGET_RADIUS * TARGET_RADIUS);
 
 // Grid Configs
 pub const GRID_COLOR: Rgb<u8> = PALEVIOLETRED;
+/// Moving obstacles, defined in the map file after the grid
+pub const OBSTACLE_COLOR: Rgb<u8> = INDIANRED;
+/// Force fields, defined in the map file after the grid, are drawn as arrows
+pub const FIELD_COLOR: Rgb<u8> = LIGHTSTEELBLUE;
+/// Distance between the arrows in pixels
+pub const FIELD_ARROW_SPACING: f32 = 40.0;
+/// Arrow length in pixels per unit of force
+pub const FIELD_ARROW_SCALE: f32 = 200.0;
 pub const MAP_FILE_PATH: &str = "assets/map.txt";
+/// Vector geometry walls (and obstacles, force fields) added on top of the map,
+/// e.g `Some("assets/scene.txt")`
+pub const SCENE_FILE_PATH: Option<&str> = None;
+
+// Map Generator Configs
+pub const MAP_GENERATOR_GRID_SIZE: usize = 30;
+/// Width of maze passages in blocks
+pub const MAZE_CORRIDOR_WIDTH: usize = 2;
+/// Chance of a block initially being a wall in caves
+pub const CAVE_FILL_PROBABILITY: f32 = 0.45;
+pub const CAVE_SMOOTHING_STEPS: usize = 4;
+pub const OBSTACLE_COUNT: usize = 12;
+pub const OBSTACLE_MAX_SIZE: usize = 5;
+pub const CORRIDOR_BARRIERS: usize = 3;
+pub const CORRIDOR_GAP_SIZE: usize = 3;
+
+// Editor Configs
+pub const EDITOR_GRID_COLOR: Rgb<u8> = SLATEGREY;
+pub const EDITOR_SPAWN_COLOR: Rgb<u8> = DEEPSKYBLUE;
+/// Distance from the spawn/target within which a click starts dragging it
+pub const EDITOR_HANDLE_RADIUS: f32 = 15.0;
+
+// Physics Configs
+/// Force applied to every rocket everywhere, in pixels per frame²
+pub const GRAVITY: (f32, f32) = (0.0, 0.0);
 
 // Rocket Configs
 pub const ROCKET_LIFESPAN: usize = 200;
@@ -25,6 +123,58 @@ pub const ROCKET_STROKE_COLOR: Rgb<u8> = DIMGREY;
 pub const ROCKET_COLOR_COMPLETED: Rgb<u8> = GREENYELLOW;
 pub const ROCKET_COLOR_CRASHED: Rgb<u8> = LIGHTSLATEGREY;
 
+// Fast-forward Configs
+/// Number of whole generations run per `update` call while fast-forwarding
+pub const FAST_FORWARD_GENERATIONS: u32 = 10;
+/// While fast-forwarding, every Nth generation is played in real time,
+/// set to 0 to skip rendering for all generations
+pub const FAST_FORWARD_RENDER_INTERVAL: u32 = 50;
+
+// Trail Configs
+/// Record the trajectory of every rocket, required for drawing trails
+pub const ROCKET_RECORD_PATH: bool = true;
+/// Trajectory of the previous generation's fittest rocket
+pub const TRAIL_COLOR: Rgb<u8> = ORANGE;
+/// Trajectory of the fittest rocket across all generations
+pub const GHOST_COLOR: Rgb<u8> = LIGHTSKYBLUE;
+pub const TRAIL_WEIGHT: f32 = 2.0;
+
+// Replay Configs
+/// Number of past generations recorded for replays in the window, the oldest are dropped first
+/// Headless runs and sweeps don't record
+/// Every generation takes about `NUM_ROCKETS` * `ROCKET_LIFESPAN` * 8 bytes
+pub const REPLAY_CAPACITY: usize = 50;
+/// Playback speed bounds, in frames per update
+pub const REPLAY_MIN_SPEED: f32 = 0.125;
+pub const REPLAY_MAX_SPEED: f32 = 16.0;
+
+// Frame Export Configs
+/// Directory the rendered frames are written to, as a numbered PNG sequence
+pub const FRAME_EXPORT_DIR: &str = "frames";
+/// Frames are exported for every Nth generation, 1 exports every frame
+pub const FRAME_EXPORT_INTERVAL: u32 = 1;
+
+// Heatmap Configs
+pub const HEATMAP_ALPHA: f32 = 0.6;
+pub const HEATMAP_CSV_PATH: &str = "heatmap.csv";
+
+// Genome Configs
+/// `Direct` has a gene per frame, `ControlPoints` has a gene every `spacing` frames
+/// and interpolates the forces between them,
+/// e.g `GeneEncoding::ControlPoints { spacing: 10, interpolation: Interpolation::CatmullRom }`
+pub const GENE_ENCODING: GeneEncoding = GeneEncoding::Direct;
+/// Allow the number of genes to differ between rockets,
+/// a rocket stops thrusting and drifts once it runs out of genes
+pub const VARIABLE_GENOME_LENGTH: bool = false;
+/// Genome length bounds are in frames, i.e divided by the spacing of control points
+pub const MIN_GENOME_LENGTH: usize = 20;
+/// Genes past `ROCKET_LIFESPAN` are never used
+pub const MAX_GENOME_LENGTH: usize = ROCKET_LIFESPAN;
+/// Chance of a random gene being inserted into a child's DNA
+pub const GENE_INSERTION_PROBABILITY: f32 = 0.1;
+/// Chance of a random gene being deleted from a child's DNA
+pub const GENE_DELETION_PROBABILITY: f32 = 0.1;
+
 // Mutation Configs
 pub const MUTATION_PROBABILITY: u8 = 10;
 pub const MUTATION_VARIATION: f32 = 0.5;
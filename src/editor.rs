@@ -0,0 +1,77 @@
+//! In-window map editor
+//!
+//! Lets the user modify the `World` with the mouse,
+//! - Click/drag on empty blocks to add walls, on walls to remove them
+//! - Drag the spawn or target to move them
+
+use crate::world::{Wall, World};
+use crate::EDITOR_HANDLE_RADIUS;
+use nannou::glam::Vec2;
+use nannou::Draw;
+use std::io;
+
+/// What the mouse is currently dragging
+enum Drag {
+    Spawn,
+    Target,
+    /// Painting walls, blocks under the mouse are set to the given wall state
+    Walls(bool),
+}
+
+/// Editor state
+#[derive(Default)]
+pub struct Editor {
+    /// `None` when the mouse button isn't held down
+    drag: Option<Drag>,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Editor { drag: None }
+    }
+
+    /// Start editing at the window position `pos`
+    ///
+    /// Grabs the spawn or target if `pos` is close enough to it,
+    /// else toggles the block at `pos` and keeps painting that state while dragging
+    pub fn press(&mut self, world: &mut World, pos: Vec2) {
+        if pos.distance(world.spawn()) <= EDITOR_HANDLE_RADIUS {
+            self.drag = Some(Drag::Spawn);
+        } else if pos.distance(world.target()) <= EDITOR_HANDLE_RADIUS {
+            self.drag = Some(Drag::Target);
+        } else if let Some(block) = world.grid_position(&pos) {
+            let is_wall = !world.is_wall(block);
+            world.set_wall(block, is_wall);
+            self.drag = Some(Drag::Walls(is_wall));
+        }
+    }
+
+    /// Continue the edit started by `press` at the window position `pos`
+    pub fn drag(&mut self, world: &mut World, pos: Vec2) {
+        match self.drag {
+            Some(Drag::Spawn) => world.set_spawn(pos),
+            Some(Drag::Target) => world.set_target(pos),
+            Some(Drag::Walls(is_wall)) => {
+                if let Some(block) = world.grid_position(&pos) {
+                    world.set_wall(block, is_wall);
+                }
+            }
+            None => (),
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.drag = None;
+    }
+
+    /// Write the edited world back to the map file it was loaded from,
+    /// fails for worlds that weren't loaded from a file, refer `World::save`
+    pub fn save(&self, world: &World) -> io::Result<()> {
+        world.save()
+    }
+
+    /// Draw the editor overlay
+    pub fn draw(&self, world: &World, draw: &Draw) {
+        world.draw_grid(draw);
+    }
+}
@@ -0,0 +1,388 @@
+//! Generic genetic algorithm
+//!
+//! The evolutionary cycle of the simulation, detached from rockets,
+//! so it can evolve any kind of genome for any optimization problem
+//!
+//! Every step of the cycle is pluggable,
+//! - Fitness, a `FitnessEvaluator` scoring the whole population at once
+//! - Selection, a `Selection` strategy picking the parents
+//! - Crossover and mutation, the genome's own by default, refer `Genome`
+//!
+//! Rockets are one instance, evolving `Dna` scored by a `RocketEvaluator`,
+//! the islands of the simulation breed their rockets with the engine too
+//! but score them as they fly, refer `GeneticAlgorithm::breed`
+//!
+//! ```no_run
+//! use genetic_rockets::{GeneticAlgorithm, Genome, Tournament};
+//! use rand::Rng;
+//!
+//! /// Find the `x` maximizing `-(x - 3)²`
+//! #[derive(Clone)]
+//! struct X(f32);
+//!
+//! impl Genome for X {
+//!     fn random<R: Rng>(rng: &mut R) -> Self {
+//!         X(rng.gen_range(-10.0..10.0))
+//!     }
+//!
+//!     fn crossover<R: Rng>(first: &Self, second: &Self, _rng: &mut R) -> Self {
+//!         X((first.0 + second.0) * 0.5)
+//!     }
+//!
+//!     fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+//!         X(self.0 + rng.gen_range(-0.1..0.1))
+//!     }
+//! }
+//!
+//! let mut ga = GeneticAlgorithm::new(100, 42, |x: &X| -(x.0 - 3.0).powi(2));
+//! ga.set_selection(Tournament(3));
+//! ga.run(50);
+//! let (fitness, x) = ga.best().unwrap();
+//! println!("x = {}, fitness = {}", x.0, fitness);
+//! ```
+
+use crate::genetics::{Dna, MutationParams, Reproduction};
+use crate::parallel;
+use crate::rocket::Rocket;
+use crate::world::World;
+use crate::ROCKET_LIFESPAN;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// A candidate solution the `GeneticAlgorithm` can evolve
+/// Genomes are `Send` and `Sync` so that children can be bred in parallel
+pub trait Genome: Clone + Send + Sync {
+    /// Create a random genome, for the first generation
+    fn random<R: Rng>(rng: &mut R) -> Self;
+    /// Create a child from the genomes of two parents,
+    /// used unless the engine is given another crossover
+    fn crossover<R: Rng>(first: &Self, second: &Self, rng: &mut R) -> Self;
+    /// Slightly alter a child, used unless the engine is given another mutation
+    fn mutate<R: Rng>(&self, rng: &mut R) -> Self;
+}
+
+/// Scores the genomes of a population, the higher the fitter
+/// Evaluators are `Send` so that the engine can be run on another thread
+pub trait FitnessEvaluator<G>: Send {
+    /// Fitness of every genome in `genomes`, in order
+    fn evaluate(&mut self, genomes: &[G]) -> Vec<f32>;
+}
+
+/// Genomes scored one at a time by a function
+impl<G, F: FnMut(&G) -> f32 + Send> FitnessEvaluator<G> for F {
+    fn evaluate(&mut self, genomes: &[G]) -> Vec<f32> {
+        genomes.iter().map(self).collect()
+    }
+}
+
+/// Strategy used to pick the parents of the next generation
+pub trait Selection: Send {
+    /// Pick the indices of `count` parents given the `fitness` of every genome
+    fn select(&self, fitness: &[f32], count: usize, rng: &mut StdRng) -> Vec<usize>;
+}
+
+/// The chance of picking a genome is proportional to its fitness,
+/// negative fitness counts as zero
+///
+/// Parents are picked uniformly if no genome has a positive fitness
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RouletteWheel;
+
+impl Selection for RouletteWheel {
+    fn select(&self, fitness: &[f32], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        // Weights are normalized to avoid overflowing their sum,
+        // only the infinitely fit genomes are picked if there are any
+        let max = fitness.iter().cloned().fold(0.0, f32::max);
+        let weights: Vec<f32> = fitness
+            .iter()
+            .map(|&f| match (max.is_infinite(), f == max) {
+                (true, true) => 1.0,
+                (true, false) => 0.0,
+                _ => f.max(0.0) / max,
+            })
+            .collect();
+
+        match WeightedIndex::new(&weights) {
+            Ok(dist) => (0..count).map(|_| dist.sample(rng)).collect(),
+            Err(_) => (0..count)
+                .map(|_| rng.gen_range(0..fitness.len()))
+                .collect(),
+        }
+    }
+}
+
+/// The fittest of a number of randomly picked genomes wins
+#[derive(Clone, Copy, Debug)]
+pub struct Tournament(pub usize);
+
+impl Selection for Tournament {
+    fn select(&self, fitness: &[f32], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        (0..count)
+            .map(|_| {
+                (0..self.0.max(1))
+                    .map(|_| rng.gen_range(0..fitness.len()))
+                    .max_by(|&a, &b| fitness[a].total_cmp(&fitness[b]))
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+}
+
+type CrossoverFn<G> = Box<dyn Fn(&G, &G, &mut StdRng) -> G + Send + Sync>;
+type MutationFn<G> = Box<dyn Fn(&G, &mut StdRng) -> G + Send + Sync>;
+
+/// Evolves a population of genomes, generation by generation
+///
+/// Every generation is evaluated, then the parents of the next generation are selected,
+/// crossed over and mutated, all randomness is seeded so runs are reproducible
+pub struct GeneticAlgorithm<G: Genome> {
+    /// Genomes of the current generation
+    genomes: Vec<G>,
+    /// Number of generations evaluated
+    generation: u32,
+    /// Fitness and genome of the fittest genome across all generations
+    best: Option<(f32, G)>,
+    /// `None` if the genomes are evaluated outside of the engine, refer `breed`
+    evaluator: Option<Box<dyn FitnessEvaluator<G>>>,
+    /// `RouletteWheel` by default
+    selection: Box<dyn Selection>,
+    /// `Genome::crossover` by default
+    crossover: CrossoverFn<G>,
+    /// `Genome::mutate` by default
+    mutation: MutationFn<G>,
+    rng: StdRng,
+}
+
+impl<G: Genome + 'static> GeneticAlgorithm<G> {
+    /// Create a population of `size` random genomes scored by `evaluator`,
+    /// with all randomness seeded by `seed`
+    pub fn new(size: usize, seed: u64, evaluator: impl FitnessEvaluator<G> + 'static) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let genomes = (0..size).map(|_| G::random(&mut rng)).collect();
+        GeneticAlgorithm::with_genomes(genomes, rng.gen(), evaluator)
+    }
+
+    /// Create a population of `genomes` scored by `evaluator`,
+    /// with all randomness seeded by `seed`
+    pub fn with_genomes(
+        genomes: Vec<G>,
+        seed: u64,
+        evaluator: impl FitnessEvaluator<G> + 'static,
+    ) -> Self {
+        GeneticAlgorithm {
+            evaluator: Some(Box::new(evaluator)),
+            ..GeneticAlgorithm::unevaluated(genomes, seed)
+        }
+    }
+
+    /// Create a population of `genomes` evaluated outside of the engine,
+    /// e.g frame by frame, with all randomness seeded by `seed`
+    /// Only `breed` moves it to the next generation
+    pub fn unevaluated(genomes: Vec<G>, seed: u64) -> Self {
+        GeneticAlgorithm {
+            genomes,
+            generation: 0,
+            best: None,
+            evaluator: None,
+            selection: Box::new(RouletteWheel),
+            crossover: Box::new(|first, second, rng| G::crossover(first, second, rng)),
+            mutation: Box::new(|genome, rng| genome.mutate(rng)),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn set_selection(&mut self, selection: impl Selection + 'static) {
+        self.selection = Box::new(selection);
+    }
+
+    pub fn set_crossover(
+        &mut self,
+        crossover: impl Fn(&G, &G, &mut StdRng) -> G + Send + Sync + 'static,
+    ) {
+        self.crossover = Box::new(crossover);
+    }
+
+    pub fn set_mutation(
+        &mut self,
+        mutation: impl Fn(&G, &mut StdRng) -> G + Send + Sync + 'static,
+    ) {
+        self.mutation = Box::new(mutation);
+    }
+
+    /// Evaluate the current generation and replace it with the next one
+    /// Returns the fitness of the evaluated generation's genomes, empty without an evaluator
+    pub fn step(&mut self) -> Vec<f32> {
+        let Some(evaluator) = &mut self.evaluator else {
+            return Vec::new();
+        };
+        let fitness = evaluator.evaluate(&self.genomes);
+
+        let fittest = (0..fitness.len()).max_by(|&a, &b| fitness[a].total_cmp(&fitness[b]));
+        if let Some(i) = fittest {
+            match &self.best {
+                Some((best, _)) if *best >= fitness[i] => (),
+                _ => self.best = Some((fitness[i], self.genomes[i].clone())),
+            }
+        }
+
+        self.breed(&fitness);
+        fitness
+    }
+
+    /// Replace the current generation with the next one,
+    /// bred from parents selected on the `fitness` of every genome
+    ///
+    /// Every child is bred using its own generator,
+    /// this keeps the results identical whether children are bred serially or in parallel
+    pub fn breed(&mut self, fitness: &[f32]) {
+        self.generation += 1;
+
+        // Every child is bred from two consecutive parents
+        let size = self.genomes.len();
+        let parents = self.selection.select(fitness, size * 2, &mut self.rng);
+        let children: Vec<_> = parents
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1], self.rng.gen::<u64>()))
+            .collect();
+
+        let (genomes, crossover, mutation) = (&self.genomes, &self.crossover, &self.mutation);
+        self.genomes = parallel::map(&children, |&(first, second, seed)| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let child = crossover(&genomes[first], &genomes[second], &mut rng);
+            mutation(&child, &mut rng)
+        });
+    }
+
+    /// Evolve the population for `generations` generations
+    pub fn run(&mut self, generations: u32) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+
+    /// Genomes of the current generation, not yet evaluated
+    pub fn genomes(&self) -> &[G] {
+        &self.genomes
+    }
+
+    /// Replace the genomes of the current generation, e.g with migrants from another population
+    pub fn set_genomes(&mut self, genomes: Vec<G>) {
+        self.genomes = genomes;
+    }
+
+    /// Number of generations evaluated
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Fitness and genome of the fittest genome across all evaluated generations
+    pub fn best(&self) -> Option<(f32, &G)> {
+        self.best
+            .as_ref()
+            .map(|(fitness, genome)| (*fitness, genome))
+    }
+}
+
+/// Rocket DNA, mutated with the `MUTATION_*` configs
+impl Genome for Dna {
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        Dna::random(rng)
+    }
+
+    fn crossover<R: Rng>(first: &Self, second: &Self, rng: &mut R) -> Self {
+        <Dna as Reproduction>::crossover(first, second, rng)
+    }
+
+    fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+        <Dna as Reproduction>::mutate(self, rng, MutationParams::default())
+    }
+}
+
+/// Scores rocket DNA by flying a rocket per genome through a world
+/// for a whole generation of `ROCKET_LIFESPAN` frames,
+/// the rockets don't see each other so they are flown independently
+pub struct RocketEvaluator {
+    world: World,
+}
+
+impl RocketEvaluator {
+    pub fn new(world: World) -> Self {
+        RocketEvaluator { world }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+}
+
+impl FitnessEvaluator<Dna> for RocketEvaluator {
+    /// `Rocket::fitness` of every genome after a whole generation, refer `Rocket::launch`
+    fn evaluate(&mut self, genomes: &[Dna]) -> Vec<f32> {
+        parallel::map(genomes, |dna| {
+            Rocket::launch(dna, &self.world, ROCKET_LIFESPAN, |_| ()).fitness()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Find the `x` maximizing `-(x - 3)²`
+    #[derive(Clone)]
+    struct X(f32);
+
+    impl Genome for X {
+        fn random<R: Rng>(rng: &mut R) -> Self {
+            X(rng.gen_range(-10.0..10.0))
+        }
+
+        fn crossover<R: Rng>(first: &Self, second: &Self, _rng: &mut R) -> Self {
+            X((first.0 + second.0) * 0.5)
+        }
+
+        fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+            X(self.0 + rng.gen_range(-0.1..0.1))
+        }
+    }
+
+    #[test]
+    fn roulette_wheel_without_positive_fitness_is_uniform() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for fitness in [vec![0.0; 10], vec![f32::NAN; 10], vec![-1.0; 10]] {
+            let mut picked = [false; 10];
+            for i in RouletteWheel.select(&fitness, 1000, &mut rng) {
+                picked[i] = true;
+            }
+            assert!(picked.iter().all(|&p| p));
+        }
+
+        let fitness = [1.0, f32::INFINITY, 5.0];
+        assert!(RouletteWheel
+            .select(&fitness, 100, &mut rng)
+            .iter()
+            .all(|&i| i == 1));
+    }
+
+    #[test]
+    fn engine_converges_and_breeds_externally_scored_genomes() {
+        let mut ga = GeneticAlgorithm::new(100, 42, |x: &X| -(x.0 - 3.0).powi(2));
+        ga.set_selection(Tournament(3));
+        ga.run(50);
+        let (fitness, x) = ga.best().unwrap();
+        assert!(fitness > -0.01, "x = {}", x.0);
+        assert_eq!(ga.generation(), 50);
+
+        // Without an evaluator only the scores given to `breed` select the parents
+        let genomes: Vec<X> = (0..20).map(|i| X(i as f32)).collect();
+        let mut ga = GeneticAlgorithm::unevaluated(genomes, 42);
+        ga.set_mutation(|x, _| x.clone());
+        assert!(ga.step().is_empty());
+        let scores: Vec<f32> = (0..20).map(|i| if i == 7 { 1.0 } else { 0.0 }).collect();
+        ga.breed(&scores);
+        assert_eq!(ga.generation(), 1);
+        assert_eq!(ga.genomes().len(), 20);
+        assert!(ga.genomes().iter().all(|x| x.0 == 7.0));
+    }
+}
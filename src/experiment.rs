@@ -0,0 +1,464 @@
+//! Experiment files
+//!
+//! A single TOML file describing everything needed to reproduce a run,
+//! the world, physics, evolution parameters, seed and stopping criteria
+//! Anything left out falls back to the configs
+//!
+//! ```toml
+//! seed = 42
+//!
+//! [world]
+//! map = "race_track.txt"       # or an inline `grid = """..."""`
+//! scene = "scene.txt"          # optional
+//! spawn = [-350.0, 0.0]        # optional, overrides the map
+//! target = [330.0, 0.0]        # optional, overrides the map
+//! checkpoints = [[0.0, 200.0]] # optional, overrides the map
+//! entities = ["circle 150,150 50", "wind 0,0 100,100 0.02,0"]
+//!
+//! [physics]
+//! gravity = [0.0, -0.01]
+//!
+//! [evolution]
+//! population = 500
+//! islands = 2
+//! mutation_probability = 10
+//! mutation_variation = 0.5
+//! selection = "nsga2"          # or "fitness_proportionate"
+//! lifespan = 250               # frames per generation
+//! encoding = { control_points = { spacing = 10, interpolation = "catmull_rom" } } # or "direct"
+//! variable_genome_length = true
+//! min_genome_length = 20
+//! max_genome_length = 250
+//! evaluation = { blended = 0.3 } # or "objective", "novelty"
+//! novelty_behaviour = { trajectory = 10 } # or "end_position"
+//! novelty_neighbours = 15
+//! novelty_archive_threshold = 40.0
+//! novelty_archive_size = 1000
+//! fitness_sharing = true
+//! species_metric = "genes"     # or "trajectory"
+//! species_threshold = 0.5
+//! migration_interval = 10
+//! migrant_count = 5
+//! migration_topology = "fully_connected" # or "ring"
+//!
+//! [stopping]                   # criteria left out are disabled, at least one is required
+//! max_generations = 300
+//! success_rate = 0.9
+//! plateau_generations = 50
+//! plateau_tolerance = 0.01
+//! time_budget_secs = 60.0
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::genetics::{GeneEncoding, GenomeParams, MutationParams};
+use crate::island::{MigrationParams, Topology};
+use crate::novelty::{Behaviour, EvaluationMode, NoveltyParams};
+use crate::population::SelectionMethod;
+use crate::simulation::{EvolutionParams, Simulation};
+use crate::species::{DistanceMetric, SharingParams};
+use crate::stopping::StoppingCriteria;
+use crate::sweep::SweepSpec;
+use crate::world::World;
+use crate::{PLATEAU_TOLERANCE, SIMULATION_SEED};
+use nannou::glam::{vec2, Vec2};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+/// A parsed experiment file
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Experiment {
+    /// `SIMULATION_SEED` (or a random seed) if not set
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub world: WorldSpec,
+    #[serde(default)]
+    pub physics: PhysicsSpec,
+    #[serde(default)]
+    pub evolution: EvolutionSpec,
+    /// The `StoppingCriteria` configs are used if the section is left out,
+    /// otherwise it needs at least one criterion
+    pub stopping: Option<StoppingSpec>,
+    /// Parameter values to sweep over, refer `sweep`
+    pub sweep: Option<SweepSpec>,
+    /// Directory relative paths in the file are resolved against
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldSpec {
+    /// Path to a map file, `MAP_FILE_PATH` and `SCENE_FILE_PATH` if neither `map` nor `grid` is set
+    pub map: Option<String>,
+    /// Contents of a map file, takes precedence over `map`
+    pub grid: Option<String>,
+    /// Path to a scene file, refer `World::load_scene`
+    pub scene: Option<String>,
+    pub spawn: Option<[f32; 2]>,
+    pub target: Option<[f32; 2]>,
+    pub checkpoints: Option<Vec<[f32; 2]>>,
+    /// Obstacles, force fields and shapes, in the map file line format
+    #[serde(default)]
+    pub entities: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhysicsSpec {
+    pub gravity: Option<[f32; 2]>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvolutionSpec {
+    pub population: Option<usize>,
+    pub islands: Option<usize>,
+    pub mutation_probability: Option<u8>,
+    pub mutation_variation: Option<f32>,
+    pub selection: Option<SelectionMethod>,
+    /// Frames per generation, `ROCKET_LIFESPAN` if not set
+    pub lifespan: Option<usize>,
+    pub encoding: Option<GeneEncoding>,
+    pub variable_genome_length: Option<bool>,
+    /// Genome length bounds in frames
+    pub min_genome_length: Option<usize>,
+    pub max_genome_length: Option<usize>,
+    pub evaluation: Option<EvaluationMode>,
+    pub novelty_behaviour: Option<Behaviour>,
+    pub novelty_neighbours: Option<usize>,
+    pub novelty_archive_threshold: Option<f32>,
+    pub novelty_archive_size: Option<usize>,
+    pub fitness_sharing: Option<bool>,
+    pub species_metric: Option<DistanceMetric>,
+    pub species_threshold: Option<f32>,
+    /// Generations between migrations, 0 disables migration
+    pub migration_interval: Option<u32>,
+    pub migrant_count: Option<usize>,
+    pub migration_topology: Option<Topology>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StoppingSpec {
+    pub max_generations: Option<u32>,
+    pub success_rate: Option<f32>,
+    pub plateau_generations: Option<u32>,
+    pub plateau_tolerance: Option<f32>,
+    pub time_budget_secs: Option<f64>,
+}
+
+impl Experiment {
+    /// Parse the experiment file located at `path`
+    /// Paths within the file are relative to the file's directory
+    pub fn load(path: &str) -> io::Result<Experiment> {
+        let mut experiment = Experiment::parse(&fs::read_to_string(path)?)?;
+        experiment.base_dir = Path::new(path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(experiment)
+    }
+
+    /// Parse the contents of an experiment file,
+    /// paths within it are relative to the working directory
+    ///
+    /// Fails if the population can't give every island a rocket
+    pub fn parse(contents: &str) -> io::Result<Experiment> {
+        let experiment: Experiment =
+            toml::from_str(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        experiment.validate()?;
+        Ok(experiment)
+    }
+
+    /// Contents of an experiment file describing the experiment,
+    /// paths within it are written as they were given
+    pub fn to_toml(&self) -> io::Result<String> {
+        toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Check that every island of every run gets at least a rocket,
+    /// that generations last for at least a frame and that runs can stop
+    fn validate(&self) -> io::Result<()> {
+        if let Some(spec) = &self.stopping {
+            let criteria = [
+                spec.max_generations.is_some(),
+                spec.success_rate.is_some(),
+                spec.plateau_generations.is_some(),
+                spec.time_budget_secs.is_some(),
+            ];
+            if !criteria.contains(&true) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "At least one stopping criterion is required, runs would never stop",
+                ));
+            }
+        }
+
+        if self.evolution.lifespan == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The lifespan has to be at least a frame",
+            ));
+        }
+
+        let sweep = self.sweep.clone().unwrap_or_default();
+        for params in sweep.combinations(self.params()) {
+            if params.num_islands == 0 || params.num_rockets < params.num_islands {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "A population of {} rockets can't be split into {} islands",
+                        params.num_rockets, params.num_islands
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the world described by the experiment
+    pub fn world(&self) -> io::Result<World> {
+        let spec = &self.world;
+        let mut world = match (&spec.grid, &spec.map) {
+            (Some(grid), _) => World::from_map_str(grid)?,
+            (None, Some(map)) => World::load(&self.resolve(map))?,
+            (None, None) => World::new()?,
+        };
+
+        if let Some(scene) = &spec.scene {
+            world.load_scene(&self.resolve(scene))?;
+        }
+        if let Some(spawn) = spec.spawn {
+            world.set_spawn(to_vec2(spawn));
+        }
+        if let Some(target) = spec.target {
+            world.set_target(to_vec2(target));
+        }
+        if let Some(checkpoints) = &spec.checkpoints {
+            world.set_checkpoints(checkpoints.iter().cloned().map(to_vec2).collect());
+        }
+        for (i, entity) in spec.entities.iter().enumerate() {
+            if !world.add_entity(entity) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid entity {} of the experiment: {}", i + 1, entity),
+                ));
+            }
+        }
+        if let Some(gravity) = self.physics.gravity {
+            world.set_gravity(to_vec2(gravity));
+        }
+
+        Ok(world)
+    }
+
+    /// Evolution parameters of the experiment
+    pub fn params(&self) -> EvolutionParams {
+        let spec = &self.evolution;
+        let defaults = EvolutionParams::default();
+        EvolutionParams {
+            num_rockets: spec.population.unwrap_or(defaults.num_rockets),
+            num_islands: spec.islands.unwrap_or(defaults.num_islands),
+            mutation: MutationParams {
+                probability: spec
+                    .mutation_probability
+                    .unwrap_or(defaults.mutation.probability),
+                variation: spec
+                    .mutation_variation
+                    .unwrap_or(defaults.mutation.variation),
+            },
+            selection_method: spec.selection.unwrap_or(defaults.selection_method),
+            lifespan: spec.lifespan.unwrap_or(defaults.lifespan),
+            genome: GenomeParams {
+                encoding: spec.encoding.unwrap_or(defaults.genome.encoding),
+                variable_length: spec
+                    .variable_genome_length
+                    .unwrap_or(defaults.genome.variable_length),
+                min_length: spec.min_genome_length.unwrap_or(defaults.genome.min_length),
+                max_length: spec.max_genome_length.unwrap_or(defaults.genome.max_length),
+            },
+            evaluation_mode: spec.evaluation.unwrap_or(defaults.evaluation_mode),
+            novelty: NoveltyParams {
+                behaviour: spec.novelty_behaviour.unwrap_or(defaults.novelty.behaviour),
+                nearest_neighbours: spec
+                    .novelty_neighbours
+                    .unwrap_or(defaults.novelty.nearest_neighbours),
+                archive_threshold: spec
+                    .novelty_archive_threshold
+                    .unwrap_or(defaults.novelty.archive_threshold),
+                archive_size: spec
+                    .novelty_archive_size
+                    .unwrap_or(defaults.novelty.archive_size),
+            },
+            sharing: SharingParams {
+                enabled: spec.fitness_sharing.unwrap_or(defaults.sharing.enabled),
+                metric: spec.species_metric.unwrap_or(defaults.sharing.metric),
+                threshold: spec.species_threshold.unwrap_or(defaults.sharing.threshold),
+            },
+            migration: MigrationParams {
+                interval: spec
+                    .migration_interval
+                    .unwrap_or(defaults.migration.interval),
+                count: spec.migrant_count.unwrap_or(defaults.migration.count),
+                topology: spec
+                    .migration_topology
+                    .unwrap_or(defaults.migration.topology),
+            },
+        }
+    }
+
+    /// Seed of the experiment, `SIMULATION_SEED` or a random seed if it isn't set
+    pub fn seed(&self) -> u64 {
+        self.seed.or(SIMULATION_SEED).unwrap_or_else(random)
+    }
+
+    /// Stopping criteria of the experiment
+    pub fn stopping_criteria(&self) -> StoppingCriteria {
+        let Some(spec) = &self.stopping else {
+            return StoppingCriteria::from_configs();
+        };
+
+        StoppingCriteria {
+            max_generations: spec.max_generations,
+            success_rate: spec.success_rate,
+            plateau_generations: spec.plateau_generations,
+            plateau_tolerance: spec.plateau_tolerance.unwrap_or(PLATEAU_TOLERANCE),
+            time_budget: spec.time_budget_secs.map(Duration::from_secs_f64),
+        }
+    }
+
+    /// Create the simulation described by the experiment
+    pub fn simulation(&self) -> io::Result<Simulation> {
+        Ok(Simulation::with_params(
+            self.world()?,
+            self.seed(),
+            self.params(),
+        ))
+    }
+
+    fn resolve(&self, path: &str) -> String {
+        self.base_dir.join(path).to_string_lossy().into_owned()
+    }
+}
+
+fn to_vec2([x, y]: [f32; 2]) -> Vec2 {
+    vec2(x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genetics::Interpolation;
+
+    const EXPERIMENT_PATH: &str = "assets/experiment.toml";
+
+    #[test]
+    fn example_experiment_parses() {
+        let experiment = Experiment::load(EXPERIMENT_PATH).unwrap();
+        assert_eq!(experiment.seed(), 7);
+        assert_eq!(
+            experiment.params(),
+            EvolutionParams {
+                num_rockets: 600,
+                num_islands: 2,
+                mutation: MutationParams {
+                    probability: 12,
+                    variation: 0.4,
+                },
+                selection_method: SelectionMethod::FitnessProportionate,
+                lifespan: 250,
+                genome: GenomeParams {
+                    encoding: GeneEncoding::ControlPoints {
+                        spacing: 5,
+                        interpolation: Interpolation::CatmullRom,
+                    },
+                    ..GenomeParams::default()
+                },
+                migration: MigrationParams {
+                    interval: 15,
+                    count: 5,
+                    ..MigrationParams::default()
+                },
+                ..EvolutionParams::default()
+            }
+        );
+
+        let criteria = experiment.stopping_criteria();
+        assert_eq!(criteria.max_generations, Some(200));
+        assert_eq!(criteria.success_rate, Some(0.8));
+        assert_eq!(criteria.plateau_generations, Some(60));
+        assert_eq!(criteria.time_budget, None);
+
+        let world = experiment.world().unwrap();
+        assert_eq!(world.gravity(), vec2(0.0, -0.002));
+    }
+
+    #[test]
+    fn example_experiment_round_trips() {
+        let experiment = Experiment::parse(&fs::read_to_string(EXPERIMENT_PATH).unwrap()).unwrap();
+        let reparsed = Experiment::parse(&experiment.to_toml().unwrap()).unwrap();
+        assert_eq!(reparsed, experiment);
+    }
+
+    #[test]
+    fn evolution_settings_parse() {
+        let contents = "\
+[evolution]
+evaluation = { blended = 0.3 }
+novelty_behaviour = { trajectory = 10 }
+novelty_neighbours = 5
+fitness_sharing = true
+species_metric = \"genes\"
+species_threshold = 0.5
+variable_genome_length = true
+max_genome_length = 150
+migration_topology = \"fully_connected\"
+";
+        let params = Experiment::parse(contents).unwrap().params();
+        let defaults = EvolutionParams::default();
+        assert_eq!(params.evaluation_mode, EvaluationMode::Blended(0.3));
+        assert_eq!(params.novelty.behaviour, Behaviour::Trajectory(10));
+        assert_eq!(params.novelty.nearest_neighbours, 5);
+        assert_eq!(params.novelty.archive_size, defaults.novelty.archive_size);
+        assert_eq!(params.sharing.metric, DistanceMetric::Genes);
+        assert!(params.sharing.enabled && params.genome.variable_length);
+        assert_eq!(params.genome.max_length, 150);
+        assert_eq!(params.migration.topology, Topology::FullyConnected);
+        assert_eq!(params.lifespan, defaults.lifespan);
+
+        assert!(Experiment::parse("[evolution]\nlifespan = 0").is_err());
+    }
+
+    #[test]
+    fn empty_stopping_criteria_are_rejected() {
+        for stopping in ["", "plateau_tolerance = 0.05"] {
+            let contents = format!("[stopping]\n{}", stopping);
+            let error = Experiment::parse(&contents).unwrap_err();
+            assert!(error
+                .to_string()
+                .contains("At least one stopping criterion"));
+        }
+        assert!(Experiment::parse("[stopping]\nmax_generations = 10").is_ok());
+    }
+
+    #[test]
+    fn empty_islands_are_rejected() {
+        for evolution in [
+            "population = 0",
+            "islands = 0",
+            "population = 2\nislands = 3",
+        ] {
+            let contents = format!("[evolution]\n{}", evolution);
+            assert!(Experiment::parse(&contents).is_err(), "{}", evolution);
+        }
+
+        let contents = "[evolution]\nislands = 4\n[sweep]\npopulation = [100, 3]";
+        assert!(Experiment::parse(contents).is_err());
+        assert!(Experiment::parse("[evolution]\npopulation = 3\nislands = 3").is_ok());
+    }
+}
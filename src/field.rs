@@ -0,0 +1,293 @@
+//! Force fields
+//!
+//! Regions of the world pushing the rockets flying through them,
+//! their forces are added to the rocket's own thrust every frame
+//!
+//! Force fields are defined in the map file after the grid, one per line,
+//! positions and sizes are in window coordinates (pixels), forces in pixels per frame²,
+//! - `wind <x,y> <width,height> <fx,fy>`
+//!   A constant force inside a rectangle
+//! - `vortex <x,y> <radius> <strength>`
+//!   A force around the center, counter-clockwise if `strength` is positive
+//! - `attractor <x,y> <radius> <strength>`
+//!   A constant force towards the center, away from it (repulsor) if `strength` is negative
+//! - `well <x,y> <radius> <strength>`
+//!   A gravity well, pulling towards the center with a force of `strength / distance²`
+
+use std::fmt;
+
+use crate::obstacle::parse_vec2;
+use crate::{FIELD_ARROW_SCALE, FIELD_ARROW_SPACING, FIELD_COLOR, SCREEN_DIMENSIONS};
+use nannou::prelude::*;
+use nannou::Draw;
+
+/// A region of the world applying a force to the rockets inside it
+#[derive(Clone, Debug, PartialEq)]
+pub enum ForceField {
+    /// Constant force inside a rectangle
+    Wind {
+        center: Vec2,
+        size: Vec2,
+        force: Vec2,
+    },
+    /// Force perpendicular to the direction of the center
+    Vortex {
+        center: Vec2,
+        radius: f32,
+        strength: f32,
+    },
+    /// Constant force towards (or away from) the center
+    Attractor {
+        center: Vec2,
+        radius: f32,
+        strength: f32,
+    },
+    /// Inverse-square force towards the center
+    Well {
+        center: Vec2,
+        radius: f32,
+        strength: f32,
+    },
+}
+
+impl ForceField {
+    /// Parse a force field definition line of the map file,
+    /// returns `None` if `line` isn't a valid force field
+    pub fn parse(line: &str) -> Option<ForceField> {
+        let mut tokens = line.split_whitespace();
+        let kind = tokens.next()?;
+        let args: Vec<&str> = tokens.collect();
+
+        let field = match (kind, args.as_slice()) {
+            ("wind", [center, size, force]) => ForceField::Wind {
+                center: parse_vec2(center)?,
+                size: parse_vec2(size)?,
+                force: parse_vec2(force)?,
+            },
+            (kind, [center, radius, strength]) => {
+                let (center, radius, strength) = (
+                    parse_vec2(center)?,
+                    radius.parse().ok()?,
+                    strength.parse().ok()?,
+                );
+                match kind {
+                    "vortex" => ForceField::Vortex {
+                        center,
+                        radius,
+                        strength,
+                    },
+                    "attractor" => ForceField::Attractor {
+                        center,
+                        radius,
+                        strength,
+                    },
+                    "well" => ForceField::Well {
+                        center,
+                        radius,
+                        strength,
+                    },
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        Some(field)
+    }
+
+    /// Force applied to a rocket at the window position `pos`
+    pub fn force(&self, pos: Vec2) -> Vec2 {
+        match *self {
+            ForceField::Wind {
+                center,
+                size,
+                force,
+            } => {
+                let rel = (pos - center).abs();
+                if rel.x <= size.x * 0.5 && rel.y <= size.y * 0.5 {
+                    force
+                } else {
+                    Vec2::ZERO
+                }
+            }
+            ForceField::Vortex {
+                center,
+                radius,
+                strength,
+            } => {
+                let rel = pos - center;
+                if rel.length() > radius {
+                    return Vec2::ZERO;
+                }
+                rel.perp().normalize_or_zero() * strength
+            }
+            ForceField::Attractor {
+                center,
+                radius,
+                strength,
+            } => {
+                let rel = center - pos;
+                if rel.length() > radius {
+                    return Vec2::ZERO;
+                }
+                rel.normalize_or_zero() * strength
+            }
+            ForceField::Well {
+                center,
+                radius,
+                strength,
+            } => {
+                let rel = center - pos;
+                let distance = rel.length();
+                if distance > radius {
+                    return Vec2::ZERO;
+                }
+                // Capped at the center, where the force would be infinite
+                rel.normalize_or_zero() * (strength / (distance * distance).max(1.0))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ForceField {
+    /// Format the force field as a map file line, refer `ForceField::parse`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (kind, center, radius, strength) = match *self {
+            ForceField::Wind {
+                center,
+                size,
+                force,
+            } => {
+                return write!(
+                    f,
+                    "wind {},{} {},{} {},{}",
+                    center.x, center.y, size.x, size.y, force.x, force.y
+                );
+            }
+            ForceField::Vortex {
+                center,
+                radius,
+                strength,
+            } => ("vortex", center, radius, strength),
+            ForceField::Attractor {
+                center,
+                radius,
+                strength,
+            } => ("attractor", center, radius, strength),
+            ForceField::Well {
+                center,
+                radius,
+                strength,
+            } => ("well", center, radius, strength),
+        };
+        write!(
+            f,
+            "{} {},{} {} {}",
+            kind, center.x, center.y, radius, strength
+        )
+    }
+}
+
+/// Sum of the forces of `fields` at the window position `pos`
+pub fn total_force(fields: &[ForceField], pos: Vec2) -> Vec2 {
+    fields
+        .iter()
+        .fold(Vec2::ZERO, |total, field| total + field.force(pos))
+}
+
+/// Draw the forces of `fields` as arrows, sampled every `FIELD_ARROW_SPACING` pixels
+pub fn draw_arrows(fields: &[ForceField], draw: &Draw) {
+    if fields.is_empty() {
+        return;
+    }
+
+    let half_screen = (SCREEN_DIMENSIONS as f32) * 0.5;
+    let samples = (SCREEN_DIMENSIONS as f32 / FIELD_ARROW_SPACING) as usize;
+    for i in 0..samples {
+        for j in 0..samples {
+            let pos = vec2(
+                -half_screen + FIELD_ARROW_SPACING * (j as f32 + 0.5),
+                -half_screen + FIELD_ARROW_SPACING * (i as f32 + 0.5),
+            );
+            let force = total_force(fields, pos);
+            if force == Vec2::ZERO {
+                continue;
+            }
+
+            // Arrows are kept within their sample cell
+            let length = (force.length() * FIELD_ARROW_SCALE).min(FIELD_ARROW_SPACING * 0.8);
+            let half_arrow = force.normalize() * length * 0.5;
+            draw.arrow()
+                .start(pos - half_arrow)
+                .end(pos + half_arrow)
+                .weight(1.0)
+                .head_length(4.0)
+                .head_width(3.0)
+                .color(FIELD_COLOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(line: &str) -> ForceField {
+        let field = ForceField::parse(line).unwrap();
+        assert_eq!(field.to_string(), line);
+        field
+    }
+
+    #[test]
+    fn wind_is_constant_inside_its_rectangle() {
+        let wind = field("wind 0,0 100,20 0.5,-0.1");
+        assert_eq!(wind.force(vec2(-50.0, 10.0)), vec2(0.5, -0.1));
+        assert_eq!(wind.force(vec2(0.0, 11.0)), Vec2::ZERO);
+        assert_eq!(wind.force(vec2(51.0, 0.0)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn vortex_turns_counter_clockwise() {
+        let vortex = field("vortex 10,10 50 2");
+        assert_eq!(vortex.force(vec2(30.0, 10.0)), vec2(0.0, 2.0));
+        assert_eq!(vortex.force(vec2(10.0, 30.0)), vec2(-2.0, 0.0));
+        assert_eq!(
+            field("vortex 10,10 50 -2").force(vec2(30.0, 10.0)),
+            vec2(0.0, -2.0)
+        );
+        assert_eq!(vortex.force(vec2(61.0, 10.0)), Vec2::ZERO);
+        assert_eq!(vortex.force(vec2(10.0, 10.0)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn attractor_pulls_towards_its_center() {
+        let attractor = field("attractor 0,0 50 3");
+        assert_eq!(attractor.force(vec2(-40.0, 0.0)), vec2(3.0, 0.0));
+        assert_eq!(attractor.force(vec2(0.0, 5.0)), vec2(0.0, -3.0));
+        assert_eq!(
+            field("attractor 0,0 50 -3").force(vec2(0.0, 5.0)),
+            vec2(0.0, 3.0)
+        );
+        assert_eq!(attractor.force(vec2(40.0, 40.0)), Vec2::ZERO);
+        assert_eq!(attractor.force(Vec2::ZERO), Vec2::ZERO);
+    }
+
+    #[test]
+    fn well_falls_off_with_the_square_of_the_distance() {
+        let well = field("well 0,0 100 400");
+        assert_eq!(well.force(vec2(10.0, 0.0)), vec2(-4.0, 0.0));
+        assert_eq!(well.force(vec2(0.0, -20.0)), vec2(0.0, 1.0));
+        assert_eq!(well.force(vec2(101.0, 0.0)), Vec2::ZERO);
+
+        // Capped close to the center and zero at it
+        assert_eq!(well.force(vec2(0.5, 0.0)), vec2(-400.0, 0.0));
+        assert_eq!(well.force(Vec2::ZERO), Vec2::ZERO);
+    }
+
+    #[test]
+    fn forces_of_overlapping_fields_add_up() {
+        let fields = [field("wind 0,0 100,100 1,0"), field("attractor 0,0 50 3")];
+        assert_eq!(total_force(&fields, vec2(0.0, 10.0)), vec2(1.0, -3.0));
+        assert_eq!(total_force(&fields, vec2(0.0, 60.0)), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,404 @@
+//! Procedural map generation
+//!
+//! Creates `World`s from a seed, so that evolution can be tested
+//! across many different environments instead of a single hand-made map
+//!
+//! Every generated map is guaranteed to have a path of no-wall blocks
+//! from the spawn to the target
+
+use std::collections::VecDeque;
+
+use crate::world::World;
+use crate::{
+    CAVE_FILL_PROBABILITY, CAVE_SMOOTHING_STEPS, CORRIDOR_BARRIERS, CORRIDOR_GAP_SIZE,
+    MAP_GENERATOR_GRID_SIZE, MAZE_CORRIDOR_WIDTH, OBSTACLE_COUNT, OBSTACLE_MAX_SIZE,
+};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// Kinds of maps the `MapGenerator` can create
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapKind {
+    /// A recursive-backtracker maze
+    Maze,
+    /// Organic caves grown by a cellular automaton
+    Caves,
+    /// Randomly placed rectangular obstacles
+    Obstacles,
+    /// Vertical barriers with a single gap each, similar to the default map
+    Corridors,
+}
+
+impl MapKind {
+    pub const ALL: [MapKind; 4] = [
+        MapKind::Maze,
+        MapKind::Caves,
+        MapKind::Obstacles,
+        MapKind::Corridors,
+    ];
+}
+
+/// A square grid of blocks, `true` represents a wall
+type Grid = Vec<Vec<bool>>;
+
+/// Seeded generator of `World`s
+///
+/// Generators created with the same seed produce the same sequence of maps
+pub struct MapGenerator {
+    rng: StdRng,
+    /// Number of rows/columns in the generated grids
+    grid_size: usize,
+}
+
+impl MapGenerator {
+    pub fn new(seed: u64) -> Self {
+        MapGenerator {
+            rng: StdRng::seed_from_u64(seed),
+            grid_size: MAP_GENERATOR_GRID_SIZE.max(MapGenerator::min_grid_size()),
+        }
+    }
+
+    /// Use a grid of `grid_size` x `grid_size` blocks for the generated maps
+    /// Smaller sizes than a single maze cell with its walls are raised to that size
+    pub fn with_grid_size(mut self, grid_size: usize) -> Self {
+        self.grid_size = grid_size.max(MapGenerator::min_grid_size());
+        self
+    }
+
+    /// Smallest grid every kind of map fits in,
+    /// a maze cell surrounded by walls, and at least 3 blocks for the others
+    fn min_grid_size() -> usize {
+        (MAZE_CORRIDOR_WIDTH + 2).max(3)
+    }
+
+    /// Generate a new map of the given `kind`
+    ///
+    /// The spawn is placed on the left and the target on the right of the map
+    pub fn generate(&mut self, kind: MapKind) -> World {
+        let mut grid = match kind {
+            MapKind::Maze => self.maze(),
+            MapKind::Caves => self.caves(),
+            MapKind::Obstacles => self.obstacles(),
+            MapKind::Corridors => self.corridors(),
+        };
+
+        // Maze cells are already free of walls
+        let (spawn, target) = self.endpoints(kind);
+        if kind != MapKind::Maze {
+            self.clear_around(&mut grid, spawn);
+            self.clear_around(&mut grid, target);
+        }
+        if !self.is_reachable(&grid, spawn, target) {
+            self.carve_path(&mut grid, spawn, target);
+        }
+
+        let mut walls = Vec::new();
+        for (i, row) in grid.iter().enumerate() {
+            for (j, &is_wall) in row.iter().enumerate() {
+                if is_wall {
+                    walls.push((i, j));
+                }
+            }
+        }
+
+        let mut world = World::from_walls(self.grid_size, walls);
+        world.set_spawn(world.grid_to_window(spawn));
+        world.set_target(world.grid_to_window(target));
+        world
+    }
+
+    /// Recursive-backtracker maze
+    /// https://en.wikipedia.org/wiki/Maze_generation_algorithm#Randomized_depth-first_search
+    ///
+    /// Maze cells are `MAZE_CORRIDOR_WIDTH` blocks wide and separated by 1 block thick walls
+    fn maze(&mut self) -> Grid {
+        let mut grid = vec![vec![true; self.grid_size]; self.grid_size];
+        let cells = self.maze_cells();
+        let mut visited = vec![vec![false; cells]; cells];
+        let mut stack = vec![(0, 0)];
+        visited[0][0] = true;
+        self.carve_cell(&mut grid, (0, 0));
+
+        while let Some(&(i, j)) = stack.last() {
+            let mut neighbours = Vec::new();
+            if i > 0 && !visited[i - 1][j] {
+                neighbours.push((i - 1, j));
+            }
+            if i + 1 < cells && !visited[i + 1][j] {
+                neighbours.push((i + 1, j));
+            }
+            if j > 0 && !visited[i][j - 1] {
+                neighbours.push((i, j - 1));
+            }
+            if j + 1 < cells && !visited[i][j + 1] {
+                neighbours.push((i, j + 1));
+            }
+
+            // Dead end, backtrack
+            let Some(&next) = neighbours.choose(&mut self.rng) else {
+                stack.pop();
+                continue;
+            };
+
+            self.carve_cell(&mut grid, next);
+            self.carve_passage(&mut grid, (i, j), next);
+            visited[next.0][next.1] = true;
+            stack.push(next);
+        }
+
+        grid
+    }
+
+    /// Cellular-automaton caves
+    /// http://www.roguebasin.com/index.php/Cellular_Automata_Method_for_Generating_Random_Cave-Like_Levels
+    ///
+    /// Blocks are randomly filled, then smoothed with the 4-5 rule,
+    /// i.e a wall survives with at least 4 wall neighbours and a no-wall block becomes a wall
+    /// with at least 5 wall neighbours
+    fn caves(&mut self) -> Grid {
+        let n = self.grid_size;
+        let mut grid: Grid = (0..n)
+            .map(|_| {
+                (0..n)
+                    .map(|_| self.rng.gen::<f32>() < CAVE_FILL_PROBABILITY)
+                    .collect()
+            })
+            .collect();
+
+        for _ in 0..CAVE_SMOOTHING_STEPS {
+            let mut smoothed = grid.clone();
+            for (i, row) in smoothed.iter_mut().enumerate() {
+                for (j, block) in row.iter_mut().enumerate() {
+                    let walls = MapGenerator::wall_neighbours(&grid, i, j);
+                    *block = walls >= 5 || (*block && walls >= 4);
+                }
+            }
+            grid = smoothed;
+        }
+
+        grid
+    }
+
+    /// Randomly placed rectangles of up to `OBSTACLE_MAX_SIZE` blocks per side
+    fn obstacles(&mut self) -> Grid {
+        let n = self.grid_size;
+        let mut grid = vec![vec![false; n]; n];
+
+        for _ in 0..OBSTACLE_COUNT {
+            let w = self.rng.gen_range(1..=OBSTACLE_MAX_SIZE.min(n));
+            let h = self.rng.gen_range(1..=OBSTACLE_MAX_SIZE.min(n));
+            let i = self.rng.gen_range(0..=n - h);
+            let j = self.rng.gen_range(0..=n - w);
+            for row in &mut grid[i..i + h] {
+                row[j..j + w].iter_mut().for_each(|b| *b = true);
+            }
+        }
+
+        grid
+    }
+
+    /// `CORRIDOR_BARRIERS` evenly spaced vertical walls between the spawn and the target,
+    /// each with a `CORRIDOR_GAP_SIZE` blocks gap at a random height
+    fn corridors(&mut self) -> Grid {
+        let n = self.grid_size;
+        let mut grid = vec![vec![false; n]; n];
+        let gap = CORRIDOR_GAP_SIZE.min(n);
+
+        for b in 1..=CORRIDOR_BARRIERS {
+            let j = b * n / (CORRIDOR_BARRIERS + 1);
+            let gap_start = self.rng.gen_range(0..=n - gap);
+            for (i, row) in grid.iter_mut().enumerate() {
+                row[j] = !(gap_start..gap_start + gap).contains(&i);
+            }
+        }
+
+        grid
+    }
+
+    /// Grid positions of the spawn and target blocks
+    fn endpoints(&self, kind: MapKind) -> ((usize, usize), (usize, usize)) {
+        let n = self.grid_size;
+        match kind {
+            // Centers of the middle-left and middle-right maze cells
+            MapKind::Maze => {
+                let cells = self.maze_cells();
+                let (top, left) = MapGenerator::cell_origin(cells / 2, 0);
+                let (_, right) = MapGenerator::cell_origin(cells / 2, cells - 1);
+                let offset = MAZE_CORRIDOR_WIDTH / 2;
+                (
+                    (top + offset, left + offset),
+                    (top + offset, right + offset),
+                )
+            }
+            _ => ((n / 2, 1), (n / 2, n - 2)),
+        }
+    }
+
+    /// Number of maze cells per row/column that fit in the grid
+    fn maze_cells(&self) -> usize {
+        ((self.grid_size.saturating_sub(1)) / (MAZE_CORRIDOR_WIDTH + 1)).max(1)
+    }
+
+    /// Grid position of the top left block of the maze cell `(i, j)`
+    fn cell_origin(i: usize, j: usize) -> (usize, usize) {
+        let pitch = MAZE_CORRIDOR_WIDTH + 1;
+        (1 + i * pitch, 1 + j * pitch)
+    }
+
+    /// Remove the walls inside the maze cell `cell`
+    fn carve_cell(&self, grid: &mut Grid, cell: (usize, usize)) {
+        let (top, left) = MapGenerator::cell_origin(cell.0, cell.1);
+        self.clear_rect(grid, top, left, MAZE_CORRIDOR_WIDTH, MAZE_CORRIDOR_WIDTH);
+    }
+
+    /// Remove the wall between two adjacent maze cells
+    fn carve_passage(&self, grid: &mut Grid, from: (usize, usize), to: (usize, usize)) {
+        let (top, left) = MapGenerator::cell_origin(from.0.min(to.0), from.1.min(to.1));
+        if from.0 != to.0 {
+            self.clear_rect(
+                grid,
+                top + MAZE_CORRIDOR_WIDTH,
+                left,
+                1,
+                MAZE_CORRIDOR_WIDTH,
+            );
+        } else {
+            self.clear_rect(
+                grid,
+                top,
+                left + MAZE_CORRIDOR_WIDTH,
+                MAZE_CORRIDOR_WIDTH,
+                1,
+            );
+        }
+    }
+
+    /// Remove the walls in the `h` x `w` rectangle with its top left block at `(top, left)`
+    /// Blocks outside the grid are ignored
+    fn clear_rect(&self, grid: &mut Grid, top: usize, left: usize, h: usize, w: usize) {
+        for row in grid.iter_mut().skip(top).take(h) {
+            row.iter_mut().skip(left).take(w).for_each(|b| *b = false);
+        }
+    }
+
+    /// Remove the walls around `block` so that rockets aren't spawned into a wall
+    fn clear_around(&self, grid: &mut Grid, (i, j): (usize, usize)) {
+        self.clear_rect(grid, i.saturating_sub(1), j.saturating_sub(1), 3, 3);
+    }
+
+    /// Count the walls among the 8 neighbours of `(i, j)`,
+    /// blocks outside the grid are counted as walls
+    fn wall_neighbours(grid: &Grid, i: usize, j: usize) -> usize {
+        let n = grid.len() as isize;
+        let mut count = 0;
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                if di == 0 && dj == 0 {
+                    continue;
+                }
+
+                let (x, y) = (i as isize + di, j as isize + dj);
+                if x < 0 || y < 0 || x >= n || y >= n || grid[x as usize][y as usize] {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Breadth first search over the no-wall blocks from `from` to `to`
+    fn is_reachable(&self, grid: &Grid, from: (usize, usize), to: (usize, usize)) -> bool {
+        let n = self.grid_size;
+        let mut visited = vec![vec![false; n]; n];
+        let mut queue = VecDeque::from([from]);
+        visited[from.0][from.1] = true;
+
+        while let Some((i, j)) = queue.pop_front() {
+            if (i, j) == to {
+                return true;
+            }
+
+            let neighbours = [
+                (i.wrapping_sub(1), j),
+                (i + 1, j),
+                (i, j.wrapping_sub(1)),
+                (i, j + 1),
+            ];
+            for (x, y) in neighbours {
+                if x >= n || y >= n || visited[x][y] || grid[x][y] {
+                    continue;
+                }
+
+                visited[x][y] = true;
+                queue.push_back((x, y));
+            }
+        }
+
+        false
+    }
+
+    /// Remove the walls along an L-shaped path from `from` to `to`,
+    /// first horizontally then vertically
+    fn carve_path(&self, grid: &mut Grid, from: (usize, usize), to: (usize, usize)) {
+        let (left, right) = (from.1.min(to.1), from.1.max(to.1));
+        let (top, bottom) = (from.0.min(to.0), from.0.max(to.0));
+        self.clear_rect(grid, from.0, left, 1, right - left + 1);
+        self.clear_rect(grid, top, to.1, bottom - top + 1, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Wall;
+
+    /// Check if there is a path of no-wall blocks from the spawn to the target of `world`
+    fn target_reachable(world: &World) -> bool {
+        let n = world.grid_size();
+        let spawn = world.grid_position(&world.spawn()).unwrap();
+        let target = world.grid_position(&world.target()).unwrap();
+        let mut visited = vec![vec![false; n]; n];
+        let mut queue = VecDeque::from([spawn]);
+        while let Some((i, j)) = queue.pop_front() {
+            if (i, j) == target {
+                return true;
+            }
+            if world.is_wall((i, j)) || visited[i][j] {
+                continue;
+            }
+            visited[i][j] = true;
+            if i > 0 {
+                queue.push_back((i - 1, j));
+            }
+            if j > 0 {
+                queue.push_back((i, j - 1));
+            }
+            if i + 1 < n {
+                queue.push_back((i + 1, j));
+            }
+            if j + 1 < n {
+                queue.push_back((i, j + 1));
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn generated_maps_reach_the_target() {
+        for grid_size in [0, 1, 2, 3, 5, 8, 17, MAP_GENERATOR_GRID_SIZE] {
+            for seed in 0..5 {
+                let mut generator = MapGenerator::new(seed).with_grid_size(grid_size);
+                for kind in MapKind::ALL {
+                    let world = generator.generate(kind);
+                    assert!(
+                        target_reachable(&world),
+                        "{:?} of size {} with seed {}",
+                        kind,
+                        grid_size,
+                        seed
+                    );
+                }
+            }
+        }
+    }
+}
@@ -6,26 +6,33 @@
 //! Refer the following link for more theory related to evolution and genetic algorithm,
 //! https://natureofcode.com/book/chapter-9-the-evolution-of-code/
 
-use crate::{MUTATION_PROBABILITY, MUTATION_VARIATION, ROCKET_LIFESPAN};
+use crate::{
+    GENE_DELETION_PROBABILITY, GENE_ENCODING, GENE_INSERTION_PROBABILITY, MAX_GENOME_LENGTH,
+    MIN_GENOME_LENGTH, MUTATION_PROBABILITY, MUTATION_VARIATION, ROCKET_LIFESPAN,
+    VARIABLE_GENOME_LENGTH,
+};
 use nannou::glam::{vec2, Vec2};
-use nannou::prelude::*;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
 
 /// Reproduction defines a strategy to create a new agent given one/more parent agents
+///
+/// All randomness is drawn from `rng`, so that reproduction is reproducible given a seed
 pub trait Reproduction {
     /// Crossover involves creating a new child given the genetic code of two parents
-    fn crossover(first: &Self, second: &Self) -> Self;
+    fn crossover<R: Rng>(first: &Self, second: &Self, rng: &mut R) -> Self;
     /// An optional step that involves performing minor variations on a crossed over child,
     /// defined by a mutation-rate
-    fn mutate(&self) -> Self;
+    fn mutate<R: Rng>(&self, rng: &mut R, params: MutationParams) -> Self;
 }
 
 /// Evolution is the mechanism by which an agent can accumulate change
 /// in its characteristics progressively over generations
 pub trait Evolution {
-    /// Create a population of N agents,
-    /// Each with randomly generated `DNA`,
+    /// Create a population of `size` agents,
+    /// Each with randomly generated `DNA`, seeded by `seed`
     /// This as generation zero
-    fn initialize() -> Self;
+    fn initialize(size: usize, seed: u64) -> Self;
     /// Rank the population of agents to
     /// calculate how well they performed in the current generation
     fn selection(&mut self);
@@ -34,11 +41,108 @@ pub trait Evolution {
     fn reproduction(&mut self);
 }
 
+/// How the genes of a DNA map to the force applied at every frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneEncoding {
+    /// Every gene is the force applied at a single frame
+    Direct,
+    /// Every gene is a control point (key-frame), placed every `spacing` frames,
+    /// the force between two control points is interpolated
+    ///
+    /// Far fewer genes are needed and the resulting trajectories are smoother
+    ControlPoints {
+        spacing: usize,
+        interpolation: Interpolation,
+    },
+}
+
+/// Interpolation between the control points of `GeneEncoding::ControlPoints`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Straight line between two control points
+    Linear,
+    /// Smooth curve passing through every control point, with uniform parameterization
+    /// https://en.wikipedia.org/wiki/Cubic_Hermite_spline#Catmull%E2%80%93Rom_spline
+    CatmullRom,
+}
+
+impl GeneEncoding {
+    /// Number of frames covered by a single gene
+    pub fn frames_per_gene(&self) -> usize {
+        match *self {
+            GeneEncoding::Direct => 1,
+            GeneEncoding::ControlPoints { spacing, .. } => spacing.max(1),
+        }
+    }
+
+    /// Number of genes needed to cover `frames` frames
+    fn genes_for(&self, frames: usize) -> usize {
+        frames.div_ceil(self.frames_per_gene()).max(1)
+    }
+}
+
+/// Rate and strength of the mutations of a DNA
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MutationParams {
+    /// Chance of a gene mutating, in thousandths per frame
+    pub probability: u8,
+    /// Maximum change of a gene vector's components
+    pub variation: f32,
+}
+
+impl Default for MutationParams {
+    /// `MUTATION_PROBABILITY` and `MUTATION_VARIATION`
+    fn default() -> Self {
+        MutationParams {
+            probability: MUTATION_PROBABILITY,
+            variation: MUTATION_VARIATION,
+        }
+    }
+}
+
+/// Layout of the genes of a new DNA
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GenomeParams {
+    pub encoding: GeneEncoding,
+    /// Allow the number of genes to differ between rockets, within the length bounds
+    pub variable_length: bool,
+    /// Genome length bounds in frames, i.e divided by the spacing of control points
+    pub min_length: usize,
+    pub max_length: usize,
+}
+
+impl Default for GenomeParams {
+    /// `GENE_ENCODING`, `VARIABLE_GENOME_LENGTH`, `MIN_GENOME_LENGTH` and `MAX_GENOME_LENGTH`
+    fn default() -> Self {
+        GenomeParams {
+            encoding: GENE_ENCODING,
+            variable_length: VARIABLE_GENOME_LENGTH,
+            min_length: MIN_GENOME_LENGTH,
+            max_length: MAX_GENOME_LENGTH,
+        }
+    }
+}
+
+impl GenomeParams {
+    /// Minimum and maximum number of genes, `None` if the length is fixed
+    fn length_bounds(&self) -> Option<(usize, usize)> {
+        let min = self.encoding.genes_for(self.min_length);
+        let max = self.encoding.genes_for(self.max_length);
+        self.variable_length.then_some((min, max.max(min)))
+    }
+}
+
 /// DNA - The genetic data encoded into a simulation agent
 #[derive(Clone)]
 pub struct Dna {
     /// Genes - A list of vectors defining the motion trajectory of an agent
     genes: Vec<Vec2>,
+    /// How `genes` map to forces at every frame
+    encoding: GeneEncoding,
+    /// Minimum and maximum number of genes, `None` if the length is fixed
+    length_bounds: Option<(usize, usize)>,
 }
 
 impl Dna {
@@ -48,26 +152,123 @@ impl Dna {
     /// Else creates a DNA with `genes`
     pub fn new(genes: Option<&Vec<Vec2>>) -> Self {
         if let Some(genes) = genes {
+            let params = GenomeParams::default();
             return Dna {
                 genes: genes.clone(),
+                encoding: params.encoding,
+                length_bounds: params.length_bounds(),
             };
         }
 
-        let genes: Vec<Vec2> = (0..ROCKET_LIFESPAN)
-            .map(|_| vec2(
-                random_range(-1.0, 1.0),
-                random_range(-1.0, 1.0)))
-            .collect();
-        Dna { genes }
+        Dna::random(&mut thread_rng())
+    }
+
+    /// Creates a new DNA with gene vectors randomized using `rng`,
+    /// laid out as defined by the `GENE_ENCODING` and `*_GENOME_LENGTH` configs
+    /// for `ROCKET_LIFESPAN` frames, refer `Dna::random_with`
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        Dna::random_with(rng, GenomeParams::default(), ROCKET_LIFESPAN)
+    }
+
+    /// Creates a new DNA with gene vectors randomized using `rng`, encoded as defined by `params`
+    ///
+    /// Has enough genes for `lifespan` frames, or for a random number of frames
+    /// within the length bounds of `params` if the length is variable
+    pub fn random_with<R: Rng>(rng: &mut R, params: GenomeParams, lifespan: usize) -> Self {
+        let encoding = params.encoding;
+        let length_bounds = params.length_bounds();
+        let length = match length_bounds {
+            Some((min, max)) => rng.gen_range(min..=max),
+            None => encoding.genes_for(lifespan),
+        };
+        let genes: Vec<Vec2> = (0..length).map(|_| Dna::random_gene(rng)).collect();
+        Dna {
+            genes,
+            encoding,
+            length_bounds,
+        }
+    }
+
+    /// Gene-wise distance to `other`,
+    /// i.e the average distance between the gene vectors at the same index
+    ///
+    /// Missing genes of the shorter DNA are treated as zero vectors, refer `get`
+    pub fn distance(&self, other: &Self) -> f32 {
+        let length = self.len().max(other.len());
+        let total: f32 = (0..length)
+            .map(|i| self.get(i).distance(other.get(i)))
+            .sum();
+        total / length.max(1) as f32
     }
 
     /// Retrieve the gene vector at `index`
-    /// Panics if `index` is invalid
+    ///
+    /// Returns a zero vector past the end of the genes,
+    /// i.e an agent with a short DNA stops thrusting and drifts
     pub fn get(&self, index: usize) -> Vec2 {
-        *self
-            .genes
-            .get(index as usize)
-            .expect("gene index out of rocket lifespan bound")
+        self.genes.get(index).cloned().unwrap_or(Vec2::ZERO)
+    }
+
+    /// Retrieve the force to be applied at frame `frame_idx`, as defined by the encoding
+    ///
+    /// Returns a zero vector past the frames covered by the genes
+    pub fn force(&self, frame_idx: usize) -> Vec2 {
+        let GeneEncoding::ControlPoints {
+            spacing,
+            interpolation,
+        } = self.encoding
+        else {
+            return self.get(frame_idx);
+        };
+
+        let spacing = spacing.max(1);
+        let idx = frame_idx / spacing;
+        if idx >= self.genes.len() {
+            return Vec2::ZERO;
+        }
+
+        // The last control point is held until the end of its segment,
+        // there is no next control point to interpolate towards
+        if idx == self.genes.len() - 1 {
+            return self.genes[idx];
+        }
+
+        let t = (frame_idx % spacing) as f32 / spacing as f32;
+        let point = |i: isize| self.genes[i.clamp(0, self.genes.len() as isize - 1) as usize];
+        let i = idx as isize;
+        match interpolation {
+            Interpolation::Linear => point(i).lerp(point(i + 1), t),
+            Interpolation::CatmullRom => {
+                let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+                let (t2, t3) = (t * t, t * t * t);
+                0.5 * ((2.0 * p1)
+                    + (p2 - p0) * t
+                    + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+                    + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+            }
+        }
+    }
+
+    /// Gene vectors, interpreted as defined by the encoding
+    pub fn genes(&self) -> &[Vec2] {
+        &self.genes
+    }
+
+    pub fn encoding(&self) -> GeneEncoding {
+        self.encoding
+    }
+
+    /// Number of genes
+    pub fn len(&self) -> usize {
+        self.genes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genes.is_empty()
+    }
+
+    fn random_gene<R: Rng>(rng: &mut R) -> Vec2 {
+        vec2(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0))
     }
 }
 
@@ -75,32 +276,163 @@ impl Reproduction for Dna {
     /// Returns the DNA of the child using `first` and `second` as parents
     ///
     /// Strategy:
-    /// 1. Pick a random mid-point
+    /// 1. Pick a random mid-point, within the length of the shorter parent
     /// 2. Then take genes from index 0 to mid-point (exclusive) from the `first`
     /// 3. And genes from mid-point to end-of-vector from `second`
-    fn crossover(first: &Self, second: &Self) -> Self {
-        let split_point = random_range(0, first.genes.len());
+    ///
+    /// The genes stay aligned to the frames they are applied at,
+    /// and the child inherits the length of `second`
+    fn crossover<R: Rng>(first: &Self, second: &Self, rng: &mut R) -> Self {
+        let split_point = rng.gen_range(0..first.len().min(second.len()).max(1));
 
-        let mut new_genes = Vec::from_iter(
-            first.genes[0..split_point].iter().cloned());
+        let mut new_genes = Vec::from_iter(first.genes[0..split_point].iter().cloned());
         new_genes.extend_from_slice(&second.genes[split_point..]);
 
-        Dna::new(Some(&new_genes))
+        Dna {
+            genes: new_genes,
+            encoding: first.encoding,
+            length_bounds: first.length_bounds,
+        }
     }
 
-    /// Returns an altered gene data based on mutation rate
-    /// defined by `MUTATION_PROBABILITY`
-    fn mutate(&self) -> Self {
+    /// Returns an altered gene data based on the mutation rate and strength of `params`
+    ///
+    /// The mutation rate is per frame, so a control point covering several frames
+    /// is proportionally more likely to mutate
+    ///
+    /// If the length is variable, a random gene may also be inserted or deleted,
+    /// shifting the genes after it by a frame (or a control point)
+    fn mutate<R: Rng>(&self, rng: &mut R, params: MutationParams) -> Self {
+        let probability =
+            (params.probability as f32) * 0.001 * self.encoding.frames_per_gene() as f32;
         let mut mutated_genes = self.genes.clone();
         for g in &mut mutated_genes {
-            if random_f32() > (MUTATION_PROBABILITY as f32) * 0.001 {
+            if rng.gen::<f32>() > probability {
                 continue;
             }
 
-            g.x += random_range(-1.0, 1.0) * MUTATION_VARIATION;
-            g.y += random_range(-1.0, 1.0) * MUTATION_VARIATION;
+            g.x += rng.gen_range(-1.0..1.0) * params.variation;
+            g.y += rng.gen_range(-1.0..1.0) * params.variation;
+        }
+
+        if let Some((min_length, max_length)) = self.length_bounds {
+            let length = mutated_genes.len();
+            if length < max_length && rng.gen::<f32>() < GENE_INSERTION_PROBABILITY {
+                let gene = Dna::random_gene(rng);
+                mutated_genes.insert(rng.gen_range(0..=length), gene);
+            }
+
+            let length = mutated_genes.len();
+            if length > min_length && rng.gen::<f32>() < GENE_DELETION_PROBABILITY {
+                mutated_genes.remove(rng.gen_range(0..length));
+            }
+        }
+
+        Dna {
+            genes: mutated_genes,
+            encoding: self.encoding,
+            length_bounds: self.length_bounds,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn interpolation_hits_every_control_point() {
+        let genes = vec![
+            vec2(1.0, -2.0),
+            vec2(-3.0, 0.5),
+            vec2(0.25, 4.0),
+            vec2(2.0, 2.0),
+        ];
+        let spacing = 7;
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            let dna = Dna {
+                genes: genes.clone(),
+                encoding: GeneEncoding::ControlPoints {
+                    spacing,
+                    interpolation,
+                },
+                length_bounds: None,
+            };
+            for (i, gene) in genes.iter().enumerate() {
+                assert_eq!(dna.force(i * spacing), *gene, "{:?}", interpolation);
+            }
+            assert_eq!(dna.force(genes.len() * spacing), Vec2::ZERO);
+        }
+    }
+
+    #[test]
+    fn last_control_point_is_held() {
+        let genes = vec![vec2(-2.0, 1.0), vec2(1.0, -1.0), vec2(3.0, 2.0)];
+        let spacing = 5;
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            let dna = Dna {
+                genes: genes.clone(),
+                encoding: GeneEncoding::ControlPoints {
+                    spacing,
+                    interpolation,
+                },
+                length_bounds: None,
+            };
+            let last = (genes.len() - 1) * spacing;
+            for frame in last..last + spacing {
+                assert_eq!(dna.force(frame), genes[2], "{:?}", interpolation);
+            }
+            assert_eq!(dna.force(last + spacing), Vec2::ZERO);
+        }
+    }
+
+    /// DNA of `length` genes `(x, 0)`, `(x, 1)`, ..., with the given length bounds
+    fn numbered(x: f32, length: usize, length_bounds: Option<(usize, usize)>) -> Dna {
+        Dna {
+            genes: (0..length).map(|i| vec2(x, i as f32)).collect(),
+            encoding: GeneEncoding::Direct,
+            length_bounds,
+        }
+    }
+
+    #[test]
+    fn crossover_splits_within_the_shorter_parent() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for (first, second) in [(10, 30), (30, 10)] {
+            let (first, second) = (numbered(1.0, first, None), numbered(2.0, second, None));
+            for _ in 0..100 {
+                let child = Dna::crossover(&first, &second, &mut rng);
+                assert_eq!(child.len(), second.len());
+
+                // Genes keep their frames, the first parent's up to the split point
+                let split = child.genes().iter().take_while(|g| g.x == 1.0).count();
+                assert!(split < first.len().min(second.len()));
+                assert_eq!(child.genes()[..split], first.genes()[..split]);
+                assert_eq!(child.genes()[split..], second.genes()[split..]);
+            }
+        }
+    }
+
+    #[test]
+    fn insertion_and_deletion_respect_the_length_bounds() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let params = MutationParams::default();
+        let (min, max) = (5, 8);
+        for length in [min, max] {
+            let mut dna = numbered(0.0, length, Some((min, max)));
+            let mut lengths = Vec::new();
+            for _ in 0..500 {
+                dna = dna.mutate(&mut rng, params);
+                lengths.push(dna.len());
+            }
+            assert!(lengths.iter().all(|l| (min..=max).contains(l)));
+            assert!(lengths.contains(&min) && lengths.contains(&max));
         }
 
-        Dna::new(Some(&mutated_genes))
+        // A fixed length never changes
+        let dna = numbered(0.0, 20, None);
+        assert!((0..100).all(|_| dna.mutate(&mut rng, params).len() == 20));
     }
 }
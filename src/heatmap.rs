@@ -0,0 +1,211 @@
+//! Heatmap of where rockets fly and crash
+//!
+//! Counts are accumulated per grid block of the `World`,
+//! both for the current generation and across all generations
+
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+
+use crate::world::World;
+use crate::HEATMAP_ALPHA;
+use nannou::prelude::*;
+use nannou::Draw;
+
+/// Which counts of the heatmap to draw/export
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatmapLayer {
+    /// Number of frames rockets spent in a block
+    Visits,
+    /// Number of rockets that crashed in a block
+    Crashes,
+}
+
+/// Per block counts, stored row by row
+#[derive(Clone)]
+struct BlockCounts {
+    visits: Vec<u32>,
+    crashes: Vec<u32>,
+}
+
+impl BlockCounts {
+    fn new(blocks: usize) -> Self {
+        BlockCounts {
+            visits: vec![0; blocks],
+            crashes: vec![0; blocks],
+        }
+    }
+
+    fn layer(&self, layer: HeatmapLayer) -> &[u32] {
+        match layer {
+            HeatmapLayer::Visits => &self.visits,
+            HeatmapLayer::Crashes => &self.crashes,
+        }
+    }
+}
+
+/// Visit and crash counts of every block in the world
+pub struct Heatmap {
+    /// Number of rows/columns, same as the world grid
+    grid_size: usize,
+    /// Counts for the current generation,
+    /// cleared at the start of every generation
+    generation: BlockCounts,
+    /// Counts across all generations
+    cumulative: BlockCounts,
+}
+
+impl Heatmap {
+    pub fn new(grid_size: usize) -> Self {
+        let counts = BlockCounts::new(grid_size * grid_size);
+        Heatmap {
+            grid_size,
+            generation: counts.clone(),
+            cumulative: counts,
+        }
+    }
+
+    /// Record a rocket spending a frame in the block `(x, y)`
+    pub fn record_visit(&mut self, (x, y): (usize, usize)) {
+        let idx = x * self.grid_size + y;
+        self.generation.visits[idx] += 1;
+        self.cumulative.visits[idx] += 1;
+    }
+
+    /// Record a rocket crashing in the block `(x, y)`
+    pub fn record_crash(&mut self, (x, y): (usize, usize)) {
+        let idx = x * self.grid_size + y;
+        self.generation.crashes[idx] += 1;
+        self.cumulative.crashes[idx] += 1;
+    }
+
+    /// Reset the counts of the current generation
+    pub fn clear_generation(&mut self) {
+        self.generation = BlockCounts::new(self.grid_size * self.grid_size);
+    }
+
+    /// Counts of `layer`, stored row by row
+    pub fn counts(&self, layer: HeatmapLayer, cumulative: bool) -> &[u32] {
+        if cumulative {
+            self.cumulative.layer(layer)
+        } else {
+            self.generation.layer(layer)
+        }
+    }
+
+    /// Draw the counts of `layer` as a color ramped overlay over the `world` blocks
+    ///
+    /// Colors range from blue (few) to red (most), on a log scale
+    /// so that a few crowded blocks don't wash out the rest
+    pub fn draw(&self, draw: &Draw, world: &World, layer: HeatmapLayer, cumulative: bool) {
+        let counts = self.counts(layer, cumulative);
+        let max = counts.iter().cloned().max().unwrap_or(0);
+        if max == 0 {
+            return;
+        }
+
+        let scale = (max as f32).ln_1p();
+        for (idx, &count) in counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+
+            let heat = (count as f32).ln_1p() / scale;
+            let pos = world.grid_to_window((idx / self.grid_size, idx % self.grid_size));
+            draw.rect()
+                .x_y(pos.x, pos.y)
+                .w_h(world.block_size(), world.block_size())
+                .color(hsla((1.0 - heat) * 0.66, 1.0, 0.5, HEATMAP_ALPHA));
+        }
+    }
+
+    /// Write the counts of `layer` to `path` as a CSV grid,
+    /// one line per row of blocks
+    pub fn write_csv(&self, path: &str, layer: HeatmapLayer, cumulative: bool) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for row in self.counts(layer, cumulative).chunks(self.grid_size) {
+            let line: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+            writeln!(writer, "{}", line.join(","))?;
+        }
+
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SCREEN_DIMENSIONS;
+
+    #[test]
+    fn counts_are_binned_per_block_and_generation() {
+        let mut heatmap = Heatmap::new(3);
+        heatmap.record_visit((0, 2));
+        heatmap.record_visit((0, 2));
+        heatmap.record_crash((2, 1));
+        assert_eq!(
+            heatmap.counts(HeatmapLayer::Visits, false),
+            &[0, 0, 2, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            heatmap.counts(HeatmapLayer::Crashes, false),
+            &[0, 0, 0, 0, 0, 0, 0, 1, 0]
+        );
+
+        heatmap.clear_generation();
+        heatmap.record_visit((1, 0));
+        assert_eq!(
+            heatmap.counts(HeatmapLayer::Visits, false),
+            &[0, 0, 0, 1, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            heatmap.counts(HeatmapLayer::Visits, true),
+            &[0, 0, 2, 1, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            heatmap.counts(HeatmapLayer::Crashes, true),
+            &[0, 0, 0, 0, 0, 0, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn positions_outside_the_grid_count_for_the_nearest_block() {
+        let world = World::from_map_str("S00\n000\n00T\n").unwrap();
+        let mut heatmap = Heatmap::new(world.grid_size());
+        let beyond = SCREEN_DIMENSIONS as f32;
+        for pos in [
+            vec2(beyond, beyond),
+            vec2(beyond, 0.0),
+            vec2(-beyond, -beyond),
+            world.grid_to_window((0, 2)),
+        ] {
+            heatmap.record_visit(world.nearest_block(&pos));
+        }
+        assert_eq!(
+            heatmap.counts(HeatmapLayer::Visits, true),
+            &[0, 0, 2, 0, 0, 1, 1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn csv_has_one_line_per_row_of_blocks() {
+        let mut heatmap = Heatmap::new(3);
+        heatmap.record_crash((0, 1));
+        heatmap.record_crash((2, 2));
+        heatmap.record_crash((2, 2));
+        heatmap.record_visit((1, 1));
+
+        let path = std::env::temp_dir().join(format!(
+            "genetic-rockets-{}-heatmap.csv",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        heatmap
+            .write_csv(path, HeatmapLayer::Crashes, true)
+            .unwrap();
+        let csv = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(csv, "0,1,0\n0,0,0\n0,0,2\n");
+    }
+}
@@ -0,0 +1,186 @@
+//! Island model evolution
+//!
+//! The rockets are split into several sub-populations (islands) that evolve independently,
+//! every few generations the best rockets of an island migrate to its neighbouring islands
+//!
+//! Isolated islands tend to converge on different routes,
+//! migration then spreads the good routes without collapsing the diversity
+//! https://en.wikipedia.org/wiki/Genetic_algorithm#Parallel_implementations
+
+use crate::population::Population;
+use crate::{MIGRANT_COUNT, MIGRATION_INTERVAL, MIGRATION_TOPOLOGY};
+use serde::{Deserialize, Serialize};
+
+/// Defines which islands migrants are sent to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    /// Every island sends migrants to the next island, the last one to the first
+    Ring,
+    /// Every island sends migrants to all other islands
+    FullyConnected,
+}
+
+impl Topology {
+    /// Indices of the islands that `island` sends migrants to, out of `count` islands
+    pub fn destinations(&self, island: usize, count: usize) -> Vec<usize> {
+        if count < 2 {
+            return Vec::new();
+        }
+
+        match self {
+            Topology::Ring => vec![(island + 1) % count],
+            Topology::FullyConnected => (0..count).filter(|&i| i != island).collect(),
+        }
+    }
+}
+
+/// When and where rockets migrate between islands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MigrationParams {
+    /// Islands exchange migrants every `interval` generations, never if 0
+    pub interval: u32,
+    /// Number of fittest rockets each island sends to every destination island
+    pub count: usize,
+    pub topology: Topology,
+}
+
+impl Default for MigrationParams {
+    /// `MIGRATION_INTERVAL`, `MIGRANT_COUNT` and `MIGRATION_TOPOLOGY`
+    fn default() -> Self {
+        MigrationParams {
+            interval: MIGRATION_INTERVAL,
+            count: MIGRANT_COUNT,
+            topology: MIGRATION_TOPOLOGY,
+        }
+    }
+}
+
+/// Send copies of the `migrant_count` best ranked rockets of every island
+/// to its destination islands, as defined by the `topology`
+///
+/// The migrants replace the lowest ranked rockets of the destination island,
+/// every island ranks its rockets the way it selects them, refer `Population::ranking`
+pub fn migrate(islands: &mut [Population], migrant_count: usize, topology: Topology) {
+    if islands.len() < 2 {
+        return;
+    }
+
+    let emigrants: Vec<_> = islands
+        .iter()
+        .map(|p| p.best_ranked(migrant_count))
+        .collect();
+
+    let mut immigrants = vec![Vec::new(); islands.len()];
+    for (source, migrants) in emigrants.into_iter().enumerate() {
+        for destination in topology.destinations(source, islands.len()) {
+            immigrants[destination].extend(migrants.iter().cloned());
+        }
+    }
+
+    for (island, migrants) in islands.iter_mut().zip(immigrants) {
+        island.replace_lowest_ranked(migrants);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{MapGenerator, MapKind};
+    use crate::genetics::Evolution;
+    use crate::heatmap::Heatmap;
+    use crate::nsga2;
+    use crate::population::SelectionMethod;
+    use crate::ROCKET_LIFESPAN;
+
+    /// Islands of `size` rockets flown for a few frames, so that their fitness differs
+    fn islands(count: usize, size: usize, selection_method: SelectionMethod) -> Vec<Population> {
+        let world = MapGenerator::new(42).generate(MapKind::Obstacles);
+        let mut heatmap = Heatmap::new(world.grid_size());
+        (0..count)
+            .map(|i| {
+                let mut island = Population::initialize(size, i as u64);
+                island.set_selection_method(selection_method);
+                island.reset(&world);
+                (1..30).for_each(|frame| {
+                    island.update(frame, &world, &mut heatmap);
+                });
+                island
+            })
+            .collect()
+    }
+
+    /// Fitness of every rocket of `island`, fittest first
+    fn fitness(island: &Population) -> Vec<f32> {
+        let mut fitness: Vec<f32> = island.rockets().iter().map(|r| r.fitness()).collect();
+        fitness.sort_by(|a, b| b.total_cmp(a));
+        fitness
+    }
+
+    #[test]
+    fn migrants_replace_the_least_fit() {
+        let (count, size, migrants) = (3, 10, 2);
+        for topology in [Topology::Ring, Topology::FullyConnected] {
+            let mut islands = islands(count, size, SelectionMethod::FitnessProportionate);
+            let before: Vec<_> = islands.iter().map(fitness).collect();
+            assert!(before.iter().all(|f| f[0] > f[size - 1]));
+            migrate(&mut islands, migrants, topology);
+
+            for (island, population) in islands.iter().enumerate() {
+                let sources: Vec<_> = (0..count)
+                    .filter(|&source| topology.destinations(source, count).contains(&island))
+                    .collect();
+                let arrived = sources.len() * migrants;
+
+                let mut expected = before[island][..size - arrived].to_vec();
+                for source in sources {
+                    expected.extend_from_slice(&before[source][..migrants]);
+                }
+                expected.sort_by(|a, b| b.total_cmp(a));
+
+                assert_eq!(population.rockets().len(), size);
+                assert_eq!(fitness(population), expected, "{:?}", topology);
+            }
+        }
+    }
+
+    #[test]
+    fn nsga2_islands_send_their_pareto_front() {
+        let (size, migrants) = (10, 2);
+        let mut islands = islands(2, size, SelectionMethod::Nsga2);
+        let objectives = |island: &Population| -> Vec<[f32; 3]> {
+            island
+                .rockets()
+                .iter()
+                .map(|r| r.objectives(ROCKET_LIFESPAN))
+                .collect()
+        };
+        let before: Vec<_> = islands.iter().map(objectives).collect();
+        migrate(&mut islands, migrants, Topology::Ring);
+
+        for (island, population) in islands.iter().enumerate() {
+            let source = &before[1 - island];
+            let ranks = nsga2::non_dominated_sort(source);
+            let arrived: Vec<_> = objectives(population)
+                .into_iter()
+                .filter(|o| !before[island].contains(o))
+                .collect();
+            assert_eq!(arrived.len(), migrants);
+            for o in arrived {
+                let i = source.iter().position(|s| *s == o).unwrap();
+                assert_eq!(ranks[i], 0, "island {}", island);
+            }
+
+            // The lowest ranked rockets made room for them
+            let ranks = nsga2::non_dominated_sort(&before[island]);
+            let kept = objectives(population);
+            let (stayed, left): (Vec<_>, Vec<_>) = before[island]
+                .iter()
+                .zip(ranks)
+                .partition(|(o, _)| kept.contains(o));
+            let worst_stayed = stayed.iter().map(|&(_, rank)| rank).max().unwrap();
+            assert_eq!(left.len(), migrants);
+            assert!(left.iter().all(|&(_, rank)| rank >= worst_stayed));
+        }
+    }
+}
@@ -1,9 +1,81 @@
+//! Genetic Rockets
+//!
+//! A genetic algorithm evolving rockets that fly through a world to reach a target
+//!
+//! The simulation can be embedded in other programs and driven without a window,
+//!
+//! ```no_run
+//! use genetic_rockets::{EvolutionParams, Simulation, World};
+//!
+//! // Construct from the configs, or from an experiment file with `Simulation::load_experiment`
+//! let world = World::load("assets/race_track.txt").unwrap();
+//! let mut simulation = Simulation::with_params(world, 42, EvolutionParams::default());
+//!
+//! // Step a single frame, or whole generations
+//! simulation.update();
+//! simulation.run_generations(10);
+//!
+//! // Inspect the rockets and read the stats
+//! for rocket in simulation.rockets() {
+//!     println!("{} {:?} {}", rocket.pos, rocket.state(), rocket.fitness());
+//! }
+//! println!("{:?}", simulation.stats());
+//!
+//! // Carry the fittest genome over to the next generation
+//! let fittest = simulation
+//!     .rockets()
+//!     .max_by(|a, b| a.fitness().total_cmp(&b.fitness()))
+//!     .unwrap();
+//! simulation.inject_genomes(vec![fittest.dna().clone()]);
+//! ```
+
 mod configs;
+mod editor;
+mod engine;
+mod experiment;
+mod field;
+mod generator;
 mod genetics;
+mod heatmap;
+mod island;
+mod novelty;
+mod nsga2;
+mod observer;
+mod obstacle;
+mod parallel;
 mod population;
+mod replay;
 mod rocket;
+mod shape;
 mod simulation;
+mod species;
+mod stopping;
+mod sweep;
 mod world;
 
 pub use configs::*;
-pub use simulation::Simulation;
+pub use editor::Editor;
+pub use engine::{
+    FitnessEvaluator, GeneticAlgorithm, Genome, RocketEvaluator, RouletteWheel, Selection,
+    Tournament,
+};
+pub use experiment::{EvolutionSpec, Experiment, PhysicsSpec, StoppingSpec, WorldSpec};
+pub use field::ForceField;
+pub use generator::{MapGenerator, MapKind};
+pub use genetics::{
+    Dna, Evolution, GeneEncoding, GenomeParams, Interpolation, MutationParams, Reproduction,
+};
+pub use heatmap::{Heatmap, HeatmapLayer};
+pub use island::{MigrationParams, Topology};
+pub use novelty::{Behaviour, EvaluationMode, NoveltyParams};
+pub use observer::{ProgressLogger, RocketEvent, SimulationObserver};
+pub use obstacle::Obstacle;
+pub use population::{Population, SelectionMethod};
+pub use replay::{Recording, Replay};
+pub use rocket::{Rocket, RocketState};
+pub use shape::Shape;
+pub use simulation::{EvolutionParams, Simulation, SimulationStats};
+pub use species::{DistanceMetric, SharingParams};
+pub use stopping::{RunProgress, RunSummary, StopReason, StoppingCriteria};
+pub use sweep::{run_sweep, sweep_table, write_sweep_csv, Estimate, SweepResult, SweepSpec};
+pub use world::{Wall, World};
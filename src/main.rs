@@ -9,36 +9,329 @@
 //! More on Nannou in the link below,
 //! https://guide.nannou.cc/tutorials/basics/anatomy-of-a-nannou-app.html
 
+use std::fs;
+use std::path::Path;
+
+use genetic_rockets::{
+    run_sweep, sweep_table, write_sweep_csv, Editor, Experiment, HeatmapLayer, MapGenerator,
+    MapKind, ProgressLogger, Replay, Simulation, StoppingCriteria,
+};
+use genetic_rockets::{
+    FAST_FORWARD_GENERATIONS, FAST_FORWARD_RENDER_INTERVAL, FRAME_EXPORT_DIR,
+    FRAME_EXPORT_INTERVAL, HEATMAP_CSV_PATH, PARETO_CSV_PATH, REPLAY_CAPACITY, SCREEN_DIMENSIONS,
+    SIM_BACKGROUND, SWEEP_CSV_PATH,
+};
 use nannou::prelude::*;
-use genetic_rockets::Simulation;
-use genetic_rockets::{SCREEN_DIMENSIONS, SIM_BACKGROUND};
+
+/// Heatmap overlays cycled through with the `H` key,
+/// the flag selects counts across all generations instead of the current generation
+const HEATMAP_VIEWS: [Option<(HeatmapLayer, bool)>; 5] = [
+    None,
+    Some((HeatmapLayer::Visits, false)),
+    Some((HeatmapLayer::Crashes, false)),
+    Some((HeatmapLayer::Visits, true)),
+    Some((HeatmapLayer::Crashes, true)),
+];
+
+/// App state
+struct Model {
+    simulation: Simulation,
+    /// When enabled, generations are run without being rendered,
+    /// toggled with the `F` key
+    fast_forward: bool,
+    /// Map editor, the simulation is paused while editing
+    /// toggled with the `E` key
+    editor: Option<Editor>,
+    /// Draw the previous generation's best trajectory and the all-time best trajectory,
+    /// toggled with the `T` key
+    show_trails: bool,
+    /// Index into `HEATMAP_VIEWS` of the heatmap overlay being drawn
+    heatmap_view_idx: usize,
+    /// Creates a new random map every time the `G` key is pressed
+    map_generator: MapGenerator,
+    /// Index into `MapKind::ALL` of the last generated map
+    map_kind_idx: usize,
+    /// Playback of a past generation, the simulation is paused while replaying
+    /// toggled with the `R` key
+    replay: Option<Replay>,
+    /// Index of the next frame exported to `FRAME_EXPORT_DIR` while exporting,
+    /// toggled with the `V` key or enabled from the start with `--export-frames`
+    frame_export: Option<u32>,
+}
 
 /// `setup` is the nannou `model` function
-/// Sets up the app state model which is `Simulation`
-fn setup(app: &App) -> Simulation {
+/// Sets up the app state model which wraps the `Simulation`
+fn setup(app: &App) -> Model {
     let _window = app
         .new_window()
         .view(view)
+        .key_pressed(key_pressed)
+        .mouse_pressed(mouse_pressed)
+        .mouse_moved(mouse_moved)
+        .mouse_released(mouse_released)
         .build()
         .expect("Failed to setup a new window");
-    Simulation::new()
+    let mut simulation = match experiment() {
+        Some(experiment) => experiment
+            .simulation()
+            .expect("Failed to load the experiment"),
+        None => Simulation::new(),
+    };
+    simulation.set_recording(REPLAY_CAPACITY);
+    Model {
+        simulation,
+        fast_forward: false,
+        editor: None,
+        show_trails: true,
+        heatmap_view_idx: 0,
+        map_generator: MapGenerator::new(random()),
+        map_kind_idx: MapKind::ALL.len() - 1,
+        replay: None,
+        frame_export: match std::env::args().any(|arg| arg == "--export-frames") {
+            true => start_frame_export(),
+            false => None,
+        },
+    }
 }
 
 /// Update app state
-fn update(_app: &App, simulation: &mut Simulation, _: Update) {
-    simulation.update();
+fn update(app: &App, model: &mut Model, _: Update) {
+    step(model);
+    export_frame(app, model);
+}
+
+/// Advance the simulation, or the replay, unless the map is being edited
+fn step(model: &mut Model) {
+    if model.editor.is_some() {
+        return;
+    }
+
+    if let Some(replay) = &mut model.replay {
+        replay.update();
+        return;
+    }
+
+    if model.fast_forward {
+        model
+            .simulation
+            .fast_forward(FAST_FORWARD_GENERATIONS, FAST_FORWARD_RENDER_INTERVAL);
+        return;
+    }
+
+    model.simulation.update();
+}
+
+/// Create `FRAME_EXPORT_DIR`, returns the index of the first frame to export
+/// or `None` if the frames can't be exported
+///
+/// Frames already in the directory are kept, the sequence continues after the last one
+fn start_frame_export() -> Option<u32> {
+    let entries = fs::create_dir_all(FRAME_EXPORT_DIR).and_then(|_| fs::read_dir(FRAME_EXPORT_DIR));
+    match entries {
+        Ok(entries) => {
+            let last = entries
+                .filter_map(|e| {
+                    let name = e.ok()?.file_name().into_string().ok()?;
+                    name.strip_suffix(".png")?.parse::<u32>().ok()
+                })
+                .max();
+            Some(last.map_or(0, |i| i + 1))
+        }
+        Err(e) => {
+            eprintln!("Failed to export the frames: {}", e);
+            None
+        }
+    }
+}
+
+/// Capture the frame rendered after this update, including the HUD,
+/// for every `FRAME_EXPORT_INTERVAL`th generation
+fn export_frame(app: &App, model: &mut Model) {
+    let Some(index) = &mut model.frame_export else {
+        return;
+    };
+
+    let generation = match &model.replay {
+        Some(replay) => replay.generation(),
+        None => model.simulation.generation_count,
+    };
+    if !generation.is_multiple_of(FRAME_EXPORT_INTERVAL.max(1)) {
+        return;
+    }
+
+    let path = Path::new(FRAME_EXPORT_DIR).join(format!("{:06}.png", index));
+    app.main_window().capture_frame(path);
+    *index += 1;
+}
+
+/// Wait for the exported frames to be written before exiting
+fn exit(app: &App, model: Model) {
+    if model.frame_export.is_some() {
+        app.main_window()
+            .await_capture_frame_jobs()
+            .expect("Failed to write the exported frames");
+    }
+}
+
+/// Handle keyboard input
+fn key_pressed(_app: &App, model: &mut Model, key: Key) {
+    if let Some(replay) = &mut model.replay {
+        if control_replay(replay, &model.simulation, key) {
+            return;
+        }
+    }
+
+    match key {
+        Key::F => model.fast_forward = !model.fast_forward,
+        Key::T => model.show_trails = !model.show_trails,
+        Key::H => model.heatmap_view_idx = (model.heatmap_view_idx + 1) % HEATMAP_VIEWS.len(),
+        Key::X => {
+            // Export the heatmap being drawn, cumulative visits if none is drawn
+            let (layer, cumulative) =
+                HEATMAP_VIEWS[model.heatmap_view_idx].unwrap_or((HeatmapLayer::Visits, true));
+            let heatmap = model.simulation.heatmap();
+            if let Err(e) = heatmap.write_csv(HEATMAP_CSV_PATH, layer, cumulative) {
+                eprintln!("Failed to export the heatmap: {}", e);
+            }
+        }
+        Key::E => {
+            model.editor = match model.editor {
+                Some(_) => None,
+                None => Some(Editor::new()),
+            }
+        }
+        Key::P => {
+            if let Err(e) = model.simulation.write_pareto_csv(PARETO_CSV_PATH) {
+                eprintln!("Failed to export the Pareto front: {}", e);
+            }
+        }
+        Key::G => {
+            // Cycle through the map kinds, restarting the simulation on every new map
+            // with the same evolution and physics
+            model.map_kind_idx = (model.map_kind_idx + 1) % MapKind::ALL.len();
+            let kind = MapKind::ALL[model.map_kind_idx];
+            let mut world = model.map_generator.generate(kind);
+            world.set_gravity(model.simulation.world().gravity());
+            model.simulation.set_world(world);
+            model.replay = None;
+        }
+        Key::V => {
+            model.frame_export = match model.frame_export {
+                Some(index) => {
+                    println!(
+                        "Exported frames up to {:06}.png to {}",
+                        index.saturating_sub(1),
+                        FRAME_EXPORT_DIR
+                    );
+                    None
+                }
+                None => start_frame_export(),
+            }
+        }
+        Key::R => {
+            // Replays start from the latest recorded generation
+            model.replay = match model.replay {
+                Some(_) => None,
+                None => {
+                    let recording = model.simulation.recording();
+                    recording.range().and_then(|(_, latest)| {
+                        let genomes = recording.get(latest)?;
+                        let (world, params) = (model.simulation.world(), model.simulation.params());
+                        Some(Replay::new(latest, genomes, world, params.lifespan))
+                    })
+                }
+            }
+        }
+        Key::S => {
+            if let Some(editor) = &model.editor {
+                if let Err(e) = editor.save(model.simulation.world()) {
+                    eprintln!("Failed to save the map: {}", e);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Handle the replay controls, returns `false` if `key` isn't one of them
+/// - `Space` plays or pauses, `.` plays forwards and `,` backwards
+/// - `Left`/`Right` step a frame, `Home`/`End` jump to the first/last frame
+/// - `Up`/`Down` switch to the next/previous recorded generation
+/// - `=`/`-` double/halve the playback speed
+fn control_replay(replay: &mut Replay, simulation: &Simulation, key: Key) -> bool {
+    match key {
+        Key::Space if replay.is_playing() => replay.pause(),
+        Key::Space => replay.play(replay.speed() < 0.0),
+        Key::Period => replay.play(false),
+        Key::Comma => replay.play(true),
+        Key::Right => replay.step(1),
+        Key::Left => replay.step(-1),
+        Key::Home => replay.seek(0),
+        Key::End => replay.seek(replay.lifespan()),
+        Key::Up | Key::Down => {
+            let generation = match key {
+                Key::Up => replay.generation() + 1,
+                _ => replay.generation().saturating_sub(1),
+            };
+            if let Some(genomes) = simulation.recording().get(generation) {
+                replay.set_generation(generation, genomes, simulation.world());
+            }
+        }
+        Key::Equals => replay.scale_speed(2.0),
+        Key::Minus => replay.scale_speed(0.5),
+        _ => return false,
+    }
+
+    true
+}
+
+/// Start a map edit at the mouse position
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    if button != MouseButton::Left {
+        return;
+    }
+
+    if let Some(editor) = &mut model.editor {
+        editor.press(model.simulation.world_mut(), app.mouse.position());
+    }
+}
+
+/// Continue a map edit, i.e drag the spawn/target or paint walls
+fn mouse_moved(_app: &App, model: &mut Model, pos: Point2) {
+    if let Some(editor) = &mut model.editor {
+        editor.drag(model.simulation.world_mut(), pos);
+    }
+}
+
+fn mouse_released(_app: &App, model: &mut Model, _button: MouseButton) {
+    if let Some(editor) = &mut model.editor {
+        editor.release();
+    }
 }
 
 /// Render the simulation on the window
-fn view(app: &App, simulation: &Simulation, frame: Frame) {
+fn view(app: &App, model: &Model, frame: Frame) {
+    let simulation = &model.simulation;
     let screen = app.main_window().rect();
     let draw = app.draw();
 
     // use frame.nth() == 0 for single background draw
     draw.background().color(SIM_BACKGROUND);
 
-    // Draw simulation
-    simulation.draw(&draw);
+    // Draw simulation, or the generation being replayed
+    match &model.replay {
+        Some(replay) => replay.draw(&draw, simulation.world()),
+        None => simulation.draw(&draw),
+    }
+    if let Some((layer, cumulative)) = HEATMAP_VIEWS[model.heatmap_view_idx] {
+        simulation.draw_heatmap(&draw, layer, cumulative);
+    }
+    if model.show_trails {
+        simulation.draw_trails(&draw);
+    }
+    if let Some(editor) = &model.editor {
+        editor.draw(simulation.world(), &draw);
+    }
 
     // Display FPS and generation
     let fps = format!("FPS: {}", app.fps().round());
@@ -51,15 +344,108 @@ fn view(app: &App, simulation: &Simulation, frame: Frame) {
         screen.bottom_right().x - 30.0,
         screen.bottom_right().y + 15.0,
     );
+    if simulation.params().sharing.enabled {
+        let species = format!("SPECIES: {}", simulation.species_count());
+        draw.text(species.as_str())
+            .x_y(screen.bottom_left().x + 45.0, screen.bottom_left().y + 15.0);
+    }
+    if simulation.params().genome.variable_length {
+        let genes = format!("GENES: {}", simulation.mean_genome_length().round());
+        draw.text(genes.as_str())
+            .x_y(screen.bottom_left().x + 45.0, screen.bottom_left().y + 30.0);
+    }
+    let saved = format!("SAVED: {}%", (simulation.saved_ratio() * 100.0).round());
+    draw.text(saved.as_str())
+        .x_y(screen.bottom_left().x + 45.0, screen.bottom_left().y + 45.0);
+    if model.editor.is_some() {
+        draw.text("EDIT").x_y(
+            screen.bottom_right().x - 30.0,
+            screen.bottom_right().y + 60.0,
+        );
+    }
+    if let Some(replay) = &model.replay {
+        let status = format!(
+            "REPLAY GEN: {} FRAME: {} SPEED: x{}",
+            replay.generation(),
+            replay.frame(),
+            replay.speed()
+        );
+        draw.text(status.as_str())
+            .w(300.0)
+            .x_y(0.0, screen.bottom() + 15.0);
+    }
+    if model.fast_forward {
+        draw.text(">> FF").x_y(
+            screen.bottom_right().x - 30.0,
+            screen.bottom_right().y + 45.0,
+        );
+    }
 
     draw.to_frame(app, &frame).unwrap();
 }
 
+/// The experiment file passed with `--experiment <path>` (or `--sweep <path>`), if any
+fn experiment() -> Option<Experiment> {
+    let args: Vec<String> = std::env::args().collect();
+    let idx = args
+        .iter()
+        .position(|arg| arg == "--experiment" || arg == "--sweep")?;
+    let path = args.get(idx + 1).expect("Missing experiment file path");
+    Some(Experiment::load(path).expect("Failed to load the experiment"))
+}
+
+/// Run every combination of parameters of the experiment's sweep,
+/// printing the aggregated results and writing them to `SWEEP_CSV_PATH`
+fn sweep(experiment: &Experiment) {
+    let results = run_sweep(experiment, |params, seed, summary| {
+        eprintln!(
+            "mutation {} x {}, {} rockets, {:?}, seed {}: {} after {} generations",
+            params.mutation.probability,
+            params.mutation.variation,
+            params.num_rockets,
+            params.selection_method,
+            seed,
+            summary.reason,
+            summary.progress.generations
+        );
+    })
+    .expect("Failed to run the sweep");
+
+    println!("{}", sweep_table(&results));
+    if let Err(e) = write_sweep_csv(SWEEP_CSV_PATH, &results) {
+        eprintln!("Failed to export the sweep results: {}", e);
+    }
+}
+
 /// Entry point,
 /// Starts the simulation
 fn main() {
+    if std::env::args().any(|arg| arg == "--sweep") {
+        sweep(&experiment().expect("Missing experiment file"));
+        return;
+    }
+
+    // Evolve without a window until a stopping criterion is met
+    if std::env::args().any(|arg| arg == "--headless") {
+        let (mut simulation, criteria) = match experiment() {
+            Some(experiment) => (
+                experiment
+                    .simulation()
+                    .expect("Failed to load the experiment"),
+                experiment.stopping_criteria(),
+            ),
+            None => (Simulation::new(), StoppingCriteria::from_configs()),
+        };
+        if std::env::args().any(|arg| arg == "--verbose") {
+            simulation.add_observer(Box::new(ProgressLogger));
+        }
+        println!("{}", simulation.run(&criteria));
+        return;
+    }
+
     nannou::app(setup)
         .size(SCREEN_DIMENSIONS, SCREEN_DIMENSIONS)
         .update(update)
+        .exit(exit)
         .run();
 }
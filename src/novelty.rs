@@ -0,0 +1,196 @@
+//! Novelty search
+//!
+//! Instead of rewarding rockets for getting close to the target,
+//! rockets are rewarded for behaving differently from the rockets seen so far
+//!
+//! Objective fitness is deceptive in maze-like maps, rockets crowd at the wall closest
+//! to the target, novelty search explores the map instead and eventually finds the route
+//! https://en.wikipedia.org/wiki/Novelty_search
+
+use crate::parallel;
+use crate::rocket::Rocket;
+use crate::{
+    NOVELTY_ARCHIVE_SIZE, NOVELTY_ARCHIVE_THRESHOLD, NOVELTY_BEHAVIOUR, NOVELTY_NEAREST_NEIGHBOURS,
+};
+use nannou::glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// How rockets are scored during selection
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaluationMode {
+    /// Closeness to the target, refer `Rocket::fitness`
+    Objective,
+    /// Novelty of the rocket's behaviour
+    Novelty,
+    /// Weighted sum of the normalized objective fitness and novelty,
+    /// the value is the weight of novelty between 0 and 1
+    Blended(f32),
+}
+
+/// Describes the behaviour of a rocket
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Behaviour {
+    /// Position of the rocket at the end of the generation
+    EndPosition,
+    /// Positions of the rocket at the given number of evenly spaced frames,
+    /// requires `ROCKET_RECORD_PATH`
+    Trajectory(usize),
+}
+
+/// Settings of novelty search
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoveltyParams {
+    pub behaviour: Behaviour,
+    /// Number of nearest behaviours novelty is measured against
+    pub nearest_neighbours: usize,
+    /// Behaviours with a novelty (in pixels) above this are added to the archive
+    pub archive_threshold: f32,
+    /// Maximum number of archived behaviours, the oldest are dropped first
+    pub archive_size: usize,
+}
+
+impl Default for NoveltyParams {
+    /// The `NOVELTY_*` configs
+    fn default() -> Self {
+        NoveltyParams {
+            behaviour: NOVELTY_BEHAVIOUR,
+            nearest_neighbours: NOVELTY_NEAREST_NEIGHBOURS,
+            archive_threshold: NOVELTY_ARCHIVE_THRESHOLD,
+            archive_size: NOVELTY_ARCHIVE_SIZE,
+        }
+    }
+}
+
+impl Behaviour {
+    /// Describe the behaviour of `rocket` during a generation of `lifespan` frames,
+    /// as a list of positions
+    pub fn describe(&self, rocket: &Rocket, lifespan: usize) -> Vec<Vec2> {
+        match *self {
+            Behaviour::EndPosition => vec![rocket.pos],
+            Behaviour::Trajectory(samples) => {
+                let path = rocket.path();
+                if path.is_empty() {
+                    return vec![rocket.pos; samples];
+                }
+
+                // A rocket that stopped early (crashed/completed) stays at its last position
+                let frames = path.len().max(lifespan);
+                (0..samples)
+                    .map(|i| {
+                        let frame = (i + 1) * frames / samples;
+                        *path.get(frame).unwrap_or(&path[path.len() - 1])
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Distance between two behaviour descriptions,
+    /// i.e the average distance between the positions
+    fn distance(a: &[Vec2], b: &[Vec2]) -> f32 {
+        let total: f32 = a.iter().zip(b).map(|(p, q)| p.distance(*q)).sum();
+        total / a.len().min(b.len()).max(1) as f32
+    }
+}
+
+/// Behaviours of past novel rockets
+///
+/// Novelty is measured against the archive as well as the current population,
+/// so that rockets aren't rewarded for going back to places visited in earlier generations
+pub struct NoveltyArchive {
+    behaviours: Vec<Vec<Vec2>>,
+    /// Maximum number of behaviours, the oldest are dropped first
+    capacity: usize,
+}
+
+impl NoveltyArchive {
+    pub fn new(capacity: usize) -> Self {
+        NoveltyArchive {
+            behaviours: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Novelty score of every behaviour in `behaviours`, without changing the archive
+    ///
+    /// The novelty of a behaviour is its average distance to the `k` nearest behaviours
+    /// among the rest of `behaviours` and the archive
+    pub fn novelty(&self, behaviours: &[Vec<Vec2>], k: usize) -> Vec<f32> {
+        let indices: Vec<usize> = (0..behaviours.len()).collect();
+        parallel::map(&indices, |&i| {
+            let mut distances: Vec<f32> = behaviours
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, b)| b)
+                .chain(&self.behaviours)
+                .map(|b| Behaviour::distance(&behaviours[i], b))
+                .collect();
+
+            let k = k.min(distances.len());
+            if k == 0 {
+                return 0.0;
+            }
+
+            distances.select_nth_unstable_by(k - 1, |a, b| a.total_cmp(b));
+            distances[..k].iter().sum::<f32>() / k as f32
+        })
+    }
+
+    /// Novelty score of every behaviour in `behaviours`, refer `novelty`
+    ///
+    /// Behaviours more novel than `threshold` are then added to the archive
+    /// and the oldest are dropped once it holds more than its capacity
+    pub fn score(&mut self, behaviours: &[Vec<Vec2>], k: usize, threshold: f32) -> Vec<f32> {
+        let scores = self.novelty(behaviours, k);
+        for (behaviour, &score) in behaviours.iter().zip(&scores) {
+            if score > threshold {
+                self.behaviours.push(behaviour.clone());
+            }
+        }
+        let excess = self.behaviours.len().saturating_sub(self.capacity);
+        self.behaviours.drain(..excess);
+
+        scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::glam::vec2;
+
+    /// End positions along the x axis
+    fn behaviours(xs: &[f32]) -> Vec<Vec<Vec2>> {
+        xs.iter().map(|&x| vec![vec2(x, 0.0)]).collect()
+    }
+
+    #[test]
+    fn novelty_is_the_mean_distance_to_the_nearest_neighbours() {
+        let archive = NoveltyArchive::new(10);
+        let behaviours = behaviours(&[0.0, 1.0, 3.0, 10.0]);
+        assert_eq!(archive.novelty(&behaviours, 2), vec![2.0, 1.5, 2.5, 8.0]);
+
+        // Fewer neighbours than `k`, all of them count
+        assert_eq!(archive.novelty(&behaviours, 10)[0], 14.0 / 3.0);
+        assert_eq!(archive.novelty(&behaviours[..1], 2), vec![0.0]);
+    }
+
+    #[test]
+    fn archive_keeps_the_latest_behaviours_above_the_threshold() {
+        let mut archive = NoveltyArchive::new(10);
+        let scores = archive.score(&behaviours(&[0.0, 1.0, 3.0, 10.0]), 2, 2.0);
+        assert_eq!(scores, vec![2.0, 1.5, 2.5, 8.0]);
+        assert_eq!(archive.behaviours, behaviours(&[3.0, 10.0]));
+
+        // Archived behaviours are neighbours of the next generations
+        assert_eq!(archive.novelty(&behaviours(&[4.0]), 2), vec![3.5]);
+
+        let mut archive = NoveltyArchive::new(2);
+        archive.score(&behaviours(&[0.0, 1.0, 3.0, 10.0]), 2, 0.0);
+        archive.score(&behaviours(&[20.0]), 2, 0.0);
+        assert_eq!(archive.behaviours, behaviours(&[10.0, 20.0]));
+    }
+}
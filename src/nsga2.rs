@@ -0,0 +1,181 @@
+//! Multi-objective optimization with NSGA-II
+//!
+//! Rockets are compared on several objectives at once (e.g distance to the target,
+//! fuel used and time taken) instead of a single fitness value,
+//! all objectives are minimized
+//!
+//! A rocket dominates another if it's no worse in every objective and better in at least one,
+//! rockets not dominated by any other form the Pareto front
+//! https://en.wikipedia.org/wiki/Multi-objective_optimization
+//! https://doi.org/10.1109/4235.996017
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+
+/// Returns `true` if `a` dominates `b`
+fn dominates<const N: usize>(a: &[f32; N], b: &[f32; N]) -> bool {
+    let no_worse = a.iter().zip(b).all(|(x, y)| x <= y);
+    let better = a.iter().zip(b).any(|(x, y)| x < y);
+    no_worse && better
+}
+
+/// Fast non-dominated sort
+///
+/// Returns the front (rank) of every solution in `objectives`,
+/// 0 being the Pareto front, 1 the front dominated only by the Pareto front and so on
+pub fn non_dominated_sort<const N: usize>(objectives: &[[f32; N]]) -> Vec<usize> {
+    let n = objectives.len();
+    // Solutions dominated by every solution
+    let mut dominated: Vec<Vec<usize>> = vec![Vec::new(); n];
+    // Number of solutions dominating every solution
+    let mut domination_count = vec![0; n];
+
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if dominates(&objectives[i], &objectives[j]) {
+                dominated[i].push(j);
+                domination_count[j] += 1;
+            } else if dominates(&objectives[j], &objectives[i]) {
+                dominated[j].push(i);
+                domination_count[i] += 1;
+            }
+        }
+    }
+
+    let mut ranks = vec![0; n];
+    let mut front: Vec<usize> = (0..n).filter(|&i| domination_count[i] == 0).collect();
+    let mut rank = 0;
+    while !front.is_empty() {
+        let mut next = Vec::new();
+        for &i in &front {
+            ranks[i] = rank;
+            for &j in &dominated[i] {
+                domination_count[j] -= 1;
+                if domination_count[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+
+        front = next;
+        rank += 1;
+    }
+
+    ranks
+}
+
+/// Crowding distance of every solution within its front
+///
+/// Measures how isolated a solution is from its neighbours in the same front,
+/// the solutions at the boundaries of a front have an infinite distance
+// `m` indexes the objectives within each solution, not `objectives` itself
+#[allow(clippy::needless_range_loop)]
+pub fn crowding_distance<const N: usize>(objectives: &[[f32; N]], ranks: &[usize]) -> Vec<f32> {
+    let mut distances = vec![0.0; objectives.len()];
+    let fronts = ranks.iter().cloned().max().map_or(0, |r| r + 1);
+
+    for rank in 0..fronts {
+        let mut front: Vec<usize> = (0..objectives.len())
+            .filter(|&i| ranks[i] == rank)
+            .collect();
+
+        for m in 0..N {
+            front.sort_by(|&a, &b| objectives[a][m].total_cmp(&objectives[b][m]));
+            let (first, last) = (front[0], front[front.len() - 1]);
+            distances[first] = f32::INFINITY;
+            distances[last] = f32::INFINITY;
+
+            let range = objectives[last][m] - objectives[first][m];
+            if range <= 0.0 {
+                continue;
+            }
+
+            for w in front.windows(3) {
+                distances[w[1]] += (objectives[w[2]][m] - objectives[w[0]][m]) / range;
+            }
+        }
+    }
+
+    distances
+}
+
+/// Crowded comparison, lower rank first and then higher crowding distance
+/// `Ordering::Less` means `a` is the better solution
+pub fn crowded_compare(a: (usize, f32), b: (usize, f32)) -> Ordering {
+    a.0.cmp(&b.0).then(b.1.total_cmp(&a.1))
+}
+
+/// Write the objectives of the solutions on a Pareto `front` to `path` as CSV,
+/// the header names the columns of every objective
+pub fn write_front_csv<const N: usize>(
+    path: &str,
+    header: [&str; N],
+    front: &[[f32; N]],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", header.join(","))?;
+    for objectives in front {
+        let line: Vec<String> = objectives.iter().map(|o| o.to_string()).collect();
+        writeln!(writer, "{}", line.join(","))?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two objectives, minimized
+    ///
+    /// ```text
+    /// 4 | e
+    /// 3 |       f
+    /// 2 |   b       d
+    /// 1 |       c
+    /// 0 | a           g
+    ///   +--------------
+    ///     0 1 2 3 4 5 6
+    /// ```
+    const POINTS: [[f32; 2]; 7] = [
+        [0.0, 0.0], // a dominates every other point but g
+        [1.0, 2.0], // b
+        [3.0, 1.0], // c
+        [5.0, 2.0], // d dominated by c
+        [0.0, 4.0], // e dominated by a alone
+        [3.0, 3.0], // f dominated by b and c
+        [6.0, 0.0], // g, same second objective as a
+    ];
+
+    #[test]
+    fn non_dominated_sort_finds_the_fronts() {
+        assert_eq!(non_dominated_sort(&POINTS), vec![0, 1, 1, 2, 1, 2, 1]);
+        assert_eq!(non_dominated_sort(&[[1.0, 1.0], [1.0, 1.0]]), vec![0, 0]);
+        assert!(non_dominated_sort::<2>(&[]).is_empty());
+    }
+
+    #[test]
+    fn crowding_distance_within_fronts() {
+        let ranks = non_dominated_sort(&POINTS);
+        let distances = crowding_distance(&POINTS, &ranks);
+
+        // Front 1 is e, b, c and g in order of the first objective, and the reverse on the second,
+        // e and g are its boundaries, their neighbours span
+        // b: (3 - 0) / 6 + (4 - 1) / 4 and c: (6 - 1) / 6 + (2 - 0) / 4
+        // Fronts 0 and 2 have no more than their boundaries
+        let inf = f32::INFINITY;
+        let expected = [inf, 1.25, 1.3333334, inf, inf, inf, inf];
+        for (distance, expected) in distances.iter().zip(expected) {
+            assert!(distance == &expected || (distance - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn crowded_compare_prefers_lower_ranks_then_isolation() {
+        assert_eq!(crowded_compare((0, 0.1), (1, 5.0)), Ordering::Less);
+        assert_eq!(crowded_compare((1, 5.0), (1, 0.1)), Ordering::Less);
+        assert_eq!(crowded_compare((2, 1.0), (2, 1.0)), Ordering::Equal);
+    }
+}
@@ -0,0 +1,152 @@
+//! Simulation observers
+//!
+//! Observers are notified of the events of a simulation as they happen,
+//! so logging, metrics and UIs can follow a simulation without changing it
+//! Register them with `Simulation::add_observer`
+//!
+//! Every callback does nothing by default, observers only implement the ones they need
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::rocket::Rocket;
+use crate::simulation::SimulationStats;
+
+/// A rocket crashing or reaching the target
+pub struct RocketEvent<'a> {
+    pub generation: u32,
+    /// Frame of the generation the event happened at
+    pub frame_idx: usize,
+    /// Index of the island the rocket belongs to
+    pub island: usize,
+    pub rocket: &'a Rocket,
+}
+
+/// Callbacks for the events of a simulation
+///
+/// Observers are `Send` so that a simulation can be run on another thread
+pub trait SimulationObserver: Send {
+    /// Called once the rockets of `generation` are placed at the spawn, before its first frame
+    fn on_generation_start(&mut self, _generation: u32) {}
+    /// Called after the last frame of `generation`, before its rockets are selected
+    fn on_generation_end(&mut self, _generation: u32, _stats: &SimulationStats) {}
+    fn on_rocket_crashed(&mut self, _event: &RocketEvent) {}
+    fn on_rocket_completed(&mut self, _event: &RocketEvent) {}
+    /// Called after `on_generation_end` when a rocket of `generation`
+    /// is fitter than every rocket of the previous generations
+    fn on_new_best_fitness(&mut self, _generation: u32, _fitness: f32) {}
+}
+
+/// Shared observers, so that their state can still be read once registered
+impl<T: SimulationObserver> SimulationObserver for Arc<Mutex<T>> {
+    fn on_generation_start(&mut self, generation: u32) {
+        lock(self).on_generation_start(generation);
+    }
+
+    fn on_generation_end(&mut self, generation: u32, stats: &SimulationStats) {
+        lock(self).on_generation_end(generation, stats);
+    }
+
+    fn on_rocket_crashed(&mut self, event: &RocketEvent) {
+        lock(self).on_rocket_crashed(event);
+    }
+
+    fn on_rocket_completed(&mut self, event: &RocketEvent) {
+        lock(self).on_rocket_completed(event);
+    }
+
+    fn on_new_best_fitness(&mut self, generation: u32, fitness: f32) {
+        lock(self).on_new_best_fitness(generation, fitness);
+    }
+}
+
+/// Lock a shared observer, even if a thread panicked while holding it
+fn lock<T>(observer: &Mutex<T>) -> MutexGuard<'_, T> {
+    observer.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Logs a line per generation and every new best fitness to stderr
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProgressLogger;
+
+impl SimulationObserver for ProgressLogger {
+    fn on_generation_end(&mut self, generation: u32, stats: &SimulationStats) {
+        eprintln!(
+            "GEN {}: {} completed, {} crashed, best fitness {:.6}",
+            generation, stats.completed, stats.crashed, stats.best_fitness
+        );
+    }
+
+    fn on_new_best_fitness(&mut self, generation: u32, fitness: f32) {
+        eprintln!("GEN {}: new best fitness {:.6}", generation, fitness);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{MapGenerator, MapKind};
+    use crate::rocket::RocketState;
+    use crate::simulation::{EvolutionParams, Simulation};
+    use std::thread;
+
+    /// Counts of the events, crashes and completions of the current generation only
+    #[derive(Default)]
+    struct Counter {
+        started: u32,
+        ended: u32,
+        crashed: usize,
+        completed: usize,
+        /// Crashed and completed rockets of the last generation's stats
+        stats: (usize, usize),
+    }
+
+    impl SimulationObserver for Counter {
+        fn on_generation_start(&mut self, _generation: u32) {
+            self.started += 1;
+            self.crashed = 0;
+            self.completed = 0;
+        }
+
+        fn on_generation_end(&mut self, _generation: u32, stats: &SimulationStats) {
+            self.ended += 1;
+            self.stats = (stats.crashed, stats.completed);
+        }
+
+        fn on_rocket_crashed(&mut self, _event: &RocketEvent) {
+            self.crashed += 1;
+        }
+
+        fn on_rocket_completed(&mut self, _event: &RocketEvent) {
+            self.completed += 1;
+        }
+    }
+
+    #[test]
+    fn shared_observer_follows_a_simulation_on_another_thread() {
+        let counter = Arc::new(Mutex::new(Counter::default()));
+        let world = MapGenerator::new(42).generate(MapKind::Corridors);
+        let params = EvolutionParams {
+            num_rockets: 20,
+            ..EvolutionParams::default()
+        };
+        let mut simulation = Simulation::with_params(world, 42, params);
+        simulation.add_observer(Box::new(counter.clone()));
+
+        let simulation = thread::spawn(move || {
+            simulation.run_generations(3);
+            simulation
+        })
+        .join()
+        .unwrap();
+
+        // Every rocket that stopped was reported once
+        let count = |state| simulation.rockets().filter(|r| r.state() == state).count();
+        let (crashed, completed) = (count(RocketState::Crashed), count(RocketState::Completed));
+        assert!(crashed > 0);
+
+        let counter = lock(&counter);
+        assert_eq!((counter.started, counter.ended), (3, 3));
+        assert_eq!((counter.crashed, counter.completed), (crashed, completed));
+        assert_eq!(counter.stats, (crashed, completed));
+    }
+}
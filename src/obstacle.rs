@@ -0,0 +1,346 @@
+//! Moving obstacles
+//!
+//! Unlike walls, the position of an obstacle depends on the frame of the generation,
+//! rockets have to evolve trajectories that pass them at the right time
+//!
+//! Obstacles are defined in the map file after the grid, one per line,
+//! positions and sizes are in window coordinates (pixels),
+//! - `patrol <size> <speed> <x,y> <x,y> ...`
+//!   A square block moving back and forth along the waypoints, `speed` in pixels per frame
+//! - `bar <x,y> <length> <width> <angular_speed>`
+//!   A bar rotating around its center, `angular_speed` in radians per frame
+//! - `door <x,y> <width,height> <period> <open_frames> <offset>`
+//!   A block that is open for `open_frames` out of every `period` frames,
+//!   starting at frame `offset`
+
+use std::fmt;
+
+use crate::OBSTACLE_COLOR;
+use nannou::prelude::*;
+use nannou::Draw;
+
+/// An obstacle whose position depends on the frame of the generation
+#[derive(Clone, Debug, PartialEq)]
+pub enum Obstacle {
+    /// Square block moving back and forth along `waypoints`
+    Patrol {
+        waypoints: Vec<Vec2>,
+        size: f32,
+        speed: f32,
+    },
+    /// Bar rotating around its `center`
+    RotatingBar {
+        center: Vec2,
+        length: f32,
+        width: f32,
+        angular_speed: f32,
+    },
+    /// Block open for `open_frames` out of every `period` frames
+    Door {
+        center: Vec2,
+        size: Vec2,
+        period: usize,
+        open_frames: usize,
+        offset: usize,
+    },
+}
+
+impl Obstacle {
+    /// Parse an obstacle definition line of the map file,
+    /// returns `None` if `line` isn't a valid obstacle
+    pub fn parse(line: &str) -> Option<Obstacle> {
+        let mut tokens = line.split_whitespace();
+        let kind = tokens.next()?;
+        let args: Vec<&str> = tokens.collect();
+
+        let obstacle = match (kind, args.as_slice()) {
+            ("patrol", [size, speed, waypoints @ ..]) if !waypoints.is_empty() => {
+                Obstacle::Patrol {
+                    waypoints: waypoints
+                        .iter()
+                        .map(|w| parse_vec2(w))
+                        .collect::<Option<_>>()?,
+                    size: size.parse().ok()?,
+                    speed: speed.parse().ok()?,
+                }
+            }
+            ("bar", [center, length, width, angular_speed]) => Obstacle::RotatingBar {
+                center: parse_vec2(center)?,
+                length: length.parse().ok()?,
+                width: width.parse().ok()?,
+                angular_speed: angular_speed.parse().ok()?,
+            },
+            ("door", [center, size, period, open_frames, offset]) => Obstacle::Door {
+                center: parse_vec2(center)?,
+                size: parse_vec2(size)?,
+                period: period.parse().ok().filter(|&p| p > 0)?,
+                open_frames: open_frames.parse().ok()?,
+                offset: offset.parse().ok()?,
+            },
+            _ => return None,
+        };
+
+        Some(obstacle)
+    }
+
+    /// Check if the obstacle covers the window position `pos` at frame `frame_idx`
+    pub fn contains(&self, pos: Vec2, frame_idx: usize) -> bool {
+        match self.bounds(frame_idx) {
+            Some((center, half_size, angle)) => {
+                let rel = to_local(pos, center, angle).abs();
+                rel.x <= half_size.x && rel.y <= half_size.y
+            }
+            None => false,
+        }
+    }
+
+    /// Check if the movement from `from` to `to` touches the obstacle
+    /// as it is at frame `frame_idx`
+    pub fn intersects(&self, from: Vec2, to: Vec2, frame_idx: usize) -> bool {
+        let Some((center, half_size, angle)) = self.bounds(frame_idx) else {
+            return false;
+        };
+        let start = to_local(from, center, angle);
+        let dir = to_local(to, center, angle) - start;
+
+        // Clip the movement by the slabs of both axes of the box
+        let (mut t_min, mut t_max) = (0.0f32, 1.0f32);
+        for (start, dir, half) in [(start.x, dir.x, half_size.x), (start.y, dir.y, half_size.y)] {
+            if dir.abs() <= f32::EPSILON {
+                if start.abs() > half {
+                    return false;
+                }
+                continue;
+            }
+            let (t1, t2) = ((-half - start) / dir, (half - start) / dir);
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+            if t_min > t_max {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Draw the obstacle as it is at frame `frame_idx`, open doors are drawn as outlines
+    pub fn draw(&self, draw: &Draw, frame_idx: usize) {
+        match self {
+            Obstacle::Patrol { size, .. } => {
+                let pos = self.position(frame_idx);
+                draw.rect()
+                    .x_y(pos.x, pos.y)
+                    .w_h(*size, *size)
+                    .color(OBSTACLE_COLOR);
+            }
+            Obstacle::RotatingBar {
+                center,
+                length,
+                width,
+                ..
+            } => {
+                draw.rect()
+                    .x_y(center.x, center.y)
+                    .w_h(*length, *width)
+                    .rotate(self.angle(frame_idx))
+                    .color(OBSTACLE_COLOR);
+            }
+            Obstacle::Door { center, size, .. } => {
+                let rect = draw.rect().x_y(center.x, center.y).w_h(size.x, size.y);
+                if self.is_open(frame_idx) {
+                    rect.no_fill().stroke(OBSTACLE_COLOR).stroke_weight(1.0);
+                } else {
+                    rect.color(OBSTACLE_COLOR);
+                }
+            }
+        }
+    }
+
+    /// Center, half size and angle of the box covered by the obstacle at frame `frame_idx`,
+    /// `None` while a door is open
+    fn bounds(&self, frame_idx: usize) -> Option<(Vec2, Vec2, f32)> {
+        match self {
+            Obstacle::Patrol { size, .. } => {
+                Some((self.position(frame_idx), Vec2::splat(size * 0.5), 0.0))
+            }
+            Obstacle::RotatingBar {
+                center,
+                length,
+                width,
+                ..
+            } => Some((*center, vec2(*length, *width) * 0.5, self.angle(frame_idx))),
+            Obstacle::Door { center, size, .. } => {
+                (!self.is_open(frame_idx)).then_some((*center, *size * 0.5, 0.0))
+            }
+        }
+    }
+
+    /// Center of a patrolling block at frame `frame_idx`
+    fn position(&self, frame_idx: usize) -> Vec2 {
+        let Obstacle::Patrol {
+            waypoints, speed, ..
+        } = self
+        else {
+            return Vec2::ZERO;
+        };
+
+        let length: f32 = waypoints.windows(2).map(|w| w[0].distance(w[1])).sum();
+        if length <= 0.0 {
+            return waypoints[0];
+        }
+
+        // Distance travelled along the waypoints, turning back at the last one
+        let mut travelled = (frame_idx as f32 * speed).rem_euclid(2.0 * length);
+        if travelled > length {
+            travelled = 2.0 * length - travelled;
+        }
+        for w in waypoints.windows(2) {
+            let segment = w[0].distance(w[1]);
+            if travelled <= segment {
+                return w[0].lerp(w[1], travelled / segment.max(f32::EPSILON));
+            }
+            travelled -= segment;
+        }
+
+        waypoints[waypoints.len() - 1]
+    }
+
+    /// Angle of a rotating bar at frame `frame_idx`
+    fn angle(&self, frame_idx: usize) -> f32 {
+        match self {
+            Obstacle::RotatingBar { angular_speed, .. } => frame_idx as f32 * angular_speed,
+            _ => 0.0,
+        }
+    }
+
+    fn is_open(&self, frame_idx: usize) -> bool {
+        match *self {
+            Obstacle::Door {
+                period,
+                open_frames,
+                offset,
+                ..
+            } => (frame_idx + period - offset % period) % period < open_frames,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Obstacle {
+    /// Format the obstacle as a map file line, refer `Obstacle::parse`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Obstacle::Patrol {
+                waypoints,
+                size,
+                speed,
+            } => {
+                write!(f, "patrol {} {}", size, speed)?;
+                for w in waypoints {
+                    write!(f, " {},{}", w.x, w.y)?;
+                }
+                Ok(())
+            }
+            Obstacle::RotatingBar {
+                center,
+                length,
+                width,
+                angular_speed,
+            } => write!(
+                f,
+                "bar {},{} {} {} {}",
+                center.x, center.y, length, width, angular_speed
+            ),
+            Obstacle::Door {
+                center,
+                size,
+                period,
+                open_frames,
+                offset,
+            } => write!(
+                f,
+                "door {},{} {},{} {} {} {}",
+                center.x, center.y, size.x, size.y, period, open_frames, offset
+            ),
+        }
+    }
+}
+
+/// Position `pos` in the frame of a box at `center` rotated by `angle`
+fn to_local(pos: Vec2, center: Vec2, angle: f32) -> Vec2 {
+    let rel = pos - center;
+    let (sin, cos) = angle.sin_cos();
+    vec2(rel.x * cos + rel.y * sin, rel.y * cos - rel.x * sin)
+}
+
+/// Parse a `x,y` pair
+pub(crate) fn parse_vec2(s: &str) -> Option<Vec2> {
+    let (x, y) = s.split_once(',')?;
+    Some(vec2(x.parse().ok()?, y.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obstacles_round_trip_through_the_map_format() {
+        for line in [
+            "patrol 20 2.5 -100,0 100,0 100,50",
+            "bar 0,-50 120 8 0.05",
+            "door 10,20 40,10 60 20 5",
+        ] {
+            let obstacle = Obstacle::parse(line).unwrap();
+            assert_eq!(obstacle.to_string(), line);
+            assert_eq!(Obstacle::parse(&obstacle.to_string()), Some(obstacle));
+        }
+        assert_eq!(Obstacle::parse("patrol 20 2.5"), None);
+        assert_eq!(Obstacle::parse("door 0,0 10,10 0 1 0"), None);
+    }
+
+    #[test]
+    fn patrol_turns_back_at_the_last_waypoint() {
+        let patrol = Obstacle::parse("patrol 10 10 0,0 100,0 100,50").unwrap();
+        assert_eq!(patrol.position(0), vec2(0.0, 0.0));
+        assert_eq!(patrol.position(5), vec2(50.0, 0.0));
+        assert_eq!(patrol.position(12), vec2(100.0, 20.0));
+        assert_eq!(patrol.position(15), vec2(100.0, 50.0));
+        assert_eq!(patrol.position(20), vec2(100.0, 0.0));
+        assert_eq!(patrol.position(30), vec2(0.0, 0.0));
+
+        assert!(patrol.contains(vec2(54.0, 4.0), 5));
+        assert!(!patrol.contains(vec2(54.0, 4.0), 0));
+    }
+
+    #[test]
+    fn bar_rotates_around_its_center() {
+        let bar = Obstacle::parse("bar 0,0 100 10 0.1").unwrap();
+        let quarter_turn = (PI / 2.0 / 0.1).round() as usize;
+        assert!(bar.contains(vec2(45.0, 0.0), 0));
+        assert!(!bar.contains(vec2(0.0, 45.0), 0));
+        assert!(bar.contains(vec2(0.0, 45.0), quarter_turn));
+        assert!(!bar.contains(vec2(45.0, 0.0), quarter_turn));
+    }
+
+    #[test]
+    fn door_is_open_for_its_open_frames() {
+        let door = Obstacle::parse("door 0,0 20,20 10 3 4").unwrap();
+        let open: Vec<usize> = (0..20).filter(|&f| !door.contains(Vec2::ZERO, f)).collect();
+        assert_eq!(open, vec![4, 5, 6, 14, 15, 16]);
+    }
+
+    #[test]
+    fn movement_through_an_obstacle_is_caught() {
+        let bar = Obstacle::parse("bar 0,0 100 4 0.0").unwrap();
+        // Jumping over the bar without ending inside it
+        assert!(bar.intersects(vec2(10.0, -10.0), vec2(10.0, 10.0), 0));
+        assert!(!bar.contains(vec2(10.0, 10.0), 0));
+        // Passing beside it or stopping short
+        assert!(!bar.intersects(vec2(60.0, -10.0), vec2(60.0, 10.0), 0));
+        assert!(!bar.intersects(vec2(10.0, -10.0), vec2(10.0, -3.0), 0));
+
+        let door = Obstacle::parse("door 0,0 20,20 10 3 0").unwrap();
+        assert!(!door.intersects(vec2(-20.0, 0.0), vec2(20.0, 0.0), 1));
+        assert!(door.intersects(vec2(-20.0, 0.0), vec2(20.0, 0.0), 5));
+    }
+}
@@ -0,0 +1,50 @@
+//! Data-parallel iteration helpers
+//!
+//! With the `parallel` feature enabled items are processed in parallel using rayon,
+//! otherwise they are processed serially
+//!
+//! Results are always returned in the order of the input items,
+//! so both paths produce identical results as long as `f` doesn't depend on ordering
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Apply `f` to every item in `items`, collecting the results
+#[cfg(feature = "parallel")]
+pub fn map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    items.par_iter().map(f).collect()
+}
+
+/// Apply `f` to every item in `items`, collecting the results
+#[cfg(not(feature = "parallel"))]
+pub fn map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    F: Fn(&T) -> R,
+{
+    items.iter().map(f).collect()
+}
+
+/// Apply `f` to a mutable reference of every item in `items`, collecting the results
+#[cfg(feature = "parallel")]
+pub fn map_mut<T, R, F>(items: &mut [T], f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(&mut T) -> R + Sync + Send,
+{
+    items.par_iter_mut().map(f).collect()
+}
+
+/// Apply `f` to a mutable reference of every item in `items`, collecting the results
+#[cfg(not(feature = "parallel"))]
+pub fn map_mut<T, R, F>(items: &mut [T], f: F) -> Vec<R>
+where
+    F: Fn(&mut T) -> R,
+{
+    items.iter_mut().map(f).collect()
+}
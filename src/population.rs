@@ -1,42 +1,362 @@
-use crate::genetics::Evolution;
-use crate::rocket::Rocket;
-use crate::world::{Wall, World};
-use crate::NUM_ROCKETS;
+use std::fmt;
+
+use crate::engine::{GeneticAlgorithm, RouletteWheel, Tournament};
+use crate::genetics::{Dna, Evolution, MutationParams, Reproduction};
+use crate::heatmap::Heatmap;
+use crate::novelty::{EvaluationMode, NoveltyArchive};
+use crate::nsga2;
+use crate::parallel;
+use crate::rocket::{Rocket, RocketState};
+use crate::simulation::EvolutionParams;
+use crate::species;
+use crate::world::World;
+use crate::{GHOST_COLOR, TRAIL_COLOR, TRAIL_WEIGHT};
+use nannou::color::Rgb;
+use nannou::glam::Vec2;
 use nannou::Draw;
-use rand::distributions::WeightedIndex;
 use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+/// Strategy used to pick the parents of the next generation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionMethod {
+    /// The chance of picking a rocket is proportional to its score,
+    /// as defined by the evaluation mode and fitness sharing of the `EvolutionParams`
+    FitnessProportionate,
+    /// Multi-objective selection over `Rocket::objectives`, refer `nsga2`
+    Nsga2,
+}
+
+impl fmt::Display for SelectionMethod {
+    /// The name of the method in experiment files
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SelectionMethod::FitnessProportionate => "fitness_proportionate",
+            SelectionMethod::Nsga2 => "nsga2",
+        };
+        write!(f, "{}", name)
+    }
+}
 
 /// A group of simulation agents
 pub struct Population {
     /// All simulation agents (Rockets) in the current generation
     agents: Vec<Rocket>,
-    /// Gene pool refers to all genes within a population
-    /// A gene pool is formulated at the end of every generation
-    /// and is then used to select the best genes for the rockets in the next generation
-    ///
-    /// `WeightedIndex` is a distribution wherein the chance of picking a given element is
-    /// proportional to weight assigned to the element
-    /// For more info refer:
-    /// https://docs.rs/rand/latest/rand/distributions/struct.WeightedIndex.html
-    gene_pool: Option<WeightedIndex<f32>>,
+    /// Breeds the next generation, refer `GeneticAlgorithm`
+    engine: GeneticAlgorithm<Dna>,
+    /// Score of every rocket the parents of the next generation are selected on,
+    /// `None` until the generation is evaluated
+    scores: Option<Vec<f32>>,
+    /// Trajectory of the fittest rocket in the previous generation
+    best_trail: Vec<Vec2>,
+    /// Fitness and trajectory of the fittest rocket across all generations
+    ghost: Option<(f32, Vec<Vec2>)>,
+    /// Number of species found in the previous generation,
+    /// 0 unless fitness sharing is enabled
+    species_count: usize,
+    /// Objectives of the rockets on the Pareto front in the previous generation,
+    /// empty unless the selection method is NSGA-II
+    pareto_front: Vec<[f32; 3]>,
+    /// Parents of the current generation, competing with it for survival under NSGA-II,
+    /// empty unless the selection method is NSGA-II
+    parents: Vec<Rocket>,
+    /// Behaviours of past novel rockets, used unless the evaluation mode is objective
+    novelty_archive: NoveltyArchive,
+    /// Parameters the population evolves with, the number of rockets and islands aside
+    params: EvolutionParams,
 }
 
 impl Population {
-    pub fn new() -> Self {
-        Population::initialize()
+    /// Create the initial set of `size` rockets, evolving as defined by `params`
+    /// All randomness in the population is seeded with `seed`
+    pub fn with_params(size: usize, seed: u64, params: EvolutionParams) -> Self {
+        // Every rocket is created using its own generator,
+        // this keeps the results identical whether rockets are created serially or in parallel
+        let mut rng = StdRng::seed_from_u64(seed);
+        let seeds: Vec<u64> = (0..size).map(|_| rng.gen()).collect();
+        let genomes = parallel::map(&seeds, |&seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            Dna::random_with(&mut rng, params.genome, params.lifespan)
+        });
+        let rockets = parallel::map(&genomes, |dna| Rocket::new(Some(dna)));
+
+        let mut population = Population {
+            agents: rockets,
+            engine: GeneticAlgorithm::unevaluated(genomes, rng.gen()),
+            scores: None,
+            best_trail: Vec::new(),
+            ghost: None,
+            species_count: 0,
+            pareto_front: Vec::new(),
+            parents: Vec::new(),
+            novelty_archive: NoveltyArchive::new(params.novelty.archive_size),
+            params,
+        };
+        population.set_mutation(params.mutation);
+        population
     }
 
     /// Update every rocket in the current generation
-    pub fn update(&mut self, frame_idx: usize, world: &World) {
+    /// and record where they fly and crash in the `heatmap`
+    ///
+    /// Returns the indices of the rockets that crashed or reached the target during the frame
+    pub fn update(&mut self, frame_idx: usize, world: &World, heatmap: &mut Heatmap) -> Vec<usize> {
+        let events = parallel::map_mut(&mut self.agents, |r| {
+            if r.state() != RocketState::Alive {
+                return None;
+            }
+
+            let block = world.nearest_block(&r.pos);
+            r.fly(frame_idx, world);
+
+            match r.state() {
+                RocketState::Alive => Some((RocketState::Alive, world.nearest_block(&r.pos))),
+                RocketState::Crashed => Some((RocketState::Crashed, block)),
+                RocketState::Completed => Some((RocketState::Completed, block)),
+            }
+        });
+
+        let mut finished = Vec::new();
+        for (i, event) in events.into_iter().enumerate() {
+            match event {
+                Some((RocketState::Alive, block)) => heatmap.record_visit(block),
+                Some((RocketState::Crashed, block)) => {
+                    heatmap.record_crash(block);
+                    finished.push(i);
+                }
+                Some((RocketState::Completed, _)) => finished.push(i),
+                None => (),
+            }
+        }
+
+        finished
+    }
+
+    /// Rockets of the current generation
+    pub fn rockets(&self) -> &[Rocket] {
+        &self.agents
+    }
+
+    /// Fraction of rockets that reached the target
+    pub fn success_rate(&self) -> f32 {
+        let completed = self
+            .agents
+            .iter()
+            .filter(|r| r.state() == RocketState::Completed)
+            .count();
+        completed as f32 / self.agents.len().max(1) as f32
+    }
+
+    /// Fitness of the fittest rocket
+    pub fn best_fitness(&self) -> f32 {
+        self.agents.iter().map(|r| r.fitness()).fold(0.0, f32::max)
+    }
+
+    /// Rate and strength of the mutations of the children, `MUTATION_*` by default
+    pub fn set_mutation(&mut self, mutation: MutationParams) {
+        self.params.mutation = mutation;
+        self.engine
+            .set_mutation(move |dna, rng| dna.mutate(rng, mutation));
+    }
+
+    pub fn set_selection_method(&mut self, selection_method: SelectionMethod) {
+        self.params.selection_method = selection_method;
+    }
+
+    /// Check if every rocket has crashed or reached the target
+    pub fn is_finished(&self) -> bool {
+        self.agents.iter().all(|r| r.state() != RocketState::Alive)
+    }
+
+    pub fn species_count(&self) -> usize {
+        self.species_count
+    }
+
+    /// Average number of genes of the rockets
+    pub fn mean_genome_length(&self) -> f32 {
+        let total: usize = self.agents.iter().map(|r| r.dna().len()).sum();
+        total as f32 / self.agents.len().max(1) as f32
+    }
+
+    pub fn pareto_front(&self) -> &[[f32; 3]] {
+        &self.pareto_front
+    }
+
+    /// Indices of the rockets of the current generation, best first,
+    /// ranked the way the selection method and evaluation mode score them
+    ///
+    /// Under NSGA-II the rockets are ranked by the crowded comparison among themselves,
+    /// unlike `selection` neither the rockets nor the novelty archive are changed
+    pub fn ranking(&self) -> Vec<usize> {
+        let mut ranking: Vec<usize> = (0..self.agents.len()).collect();
+        match self.params.selection_method {
+            SelectionMethod::FitnessProportionate => {
+                let fitness = parallel::map(&self.agents, |r| r.fitness());
+                let novelty = match self.params.evaluation_mode {
+                    EvaluationMode::Objective => Vec::new(),
+                    _ => self
+                        .novelty_archive
+                        .novelty(&self.behaviours(), self.params.novelty.nearest_neighbours),
+                };
+                let (scores, _) = self.weighted_scores(fitness, novelty);
+                ranking.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+            }
+            SelectionMethod::Nsga2 => {
+                let lifespan = self.params.lifespan;
+                let objectives = parallel::map(&self.agents, |r| r.objectives(lifespan));
+                let ranks = nsga2::non_dominated_sort(&objectives);
+                let crowding = nsga2::crowding_distance(&objectives, &ranks);
+                ranking.sort_by(|&a, &b| {
+                    nsga2::crowded_compare((ranks[a], crowding[a]), (ranks[b], crowding[b]))
+                });
+            }
+        }
+
+        ranking
+    }
+
+    /// Copies of the `count` best ranked rockets, best first, refer `ranking`
+    pub fn best_ranked(&self, count: usize) -> Vec<Rocket> {
+        self.ranking()
+            .into_iter()
+            .take(count)
+            .map(|i| self.agents[i].clone())
+            .collect()
+    }
+
+    /// Replace the lowest ranked rockets with `rockets`, refer `ranking`
+    /// At most every rocket in the population is replaced
+    pub fn replace_lowest_ranked(&mut self, rockets: Vec<Rocket>) {
+        for (i, rocket) in self.ranking().into_iter().rev().zip(rockets) {
+            self.agents[i] = rocket;
+        }
+    }
+
+    /// Replace the last rockets with `rockets`, used before the generation begins
+    /// At most every rocket in the population is replaced
+    pub fn inject(&mut self, rockets: Vec<Rocket>) {
+        let start = self.agents.len().saturating_sub(rockets.len());
+        for (slot, rocket) in self.agents[start..].iter_mut().zip(rockets) {
+            *slot = rocket;
+        }
+    }
+
+    /// Place every rocket at the `world` spawn location and point it at the `world` course
+    pub fn reset(&mut self, world: &World) {
+        let course = world.course();
         for r in &mut self.agents {
-            let is_wall = world.is_wall(&r.pos);
-            r.update(frame_idx, is_wall);
+            r.reset(world.spawn(), &course);
         }
     }
 
     /// Draw every rocket for the current generation
     pub fn draw(&self, draw: &Draw) {
-        self.agents.iter().for_each(|v| v.draw(&draw));
+        self.agents.iter().for_each(|v| v.draw(draw));
+    }
+
+    /// Draw the trajectories of the previous generation's fittest rocket
+    /// and the all-time fittest rocket (ghost)
+    pub fn draw_trails(&self, draw: &Draw) {
+        if let Some((_, ghost)) = &self.ghost {
+            Population::draw_trail(draw, ghost, GHOST_COLOR);
+        }
+        Population::draw_trail(draw, &self.best_trail, TRAIL_COLOR);
+    }
+
+    /// Behaviour of every rocket, as described by the novelty settings
+    fn behaviours(&self) -> Vec<Vec<Vec2>> {
+        let (behaviour, lifespan) = (self.params.novelty.behaviour, self.params.lifespan);
+        self.agents
+            .iter()
+            .map(|r| behaviour.describe(r, lifespan))
+            .collect()
+    }
+
+    /// Score the rockets based on the evaluation mode, given their `fitness` and `novelty`
+    /// (empty under objective evaluation),
+    /// the chance of a rocket becoming a parent is proportional to its score
+    ///
+    /// Returns the scores and the number of species, 0 unless fitness sharing is enabled
+    fn weighted_scores(&self, fitness: Vec<f32>, novelty: Vec<f32>) -> (Vec<f32>, usize) {
+        let max_fitness = fitness.iter().cloned().fold(0.0, f32::max);
+        let mut weights = match self.params.evaluation_mode {
+            EvaluationMode::Objective => fitness,
+            EvaluationMode::Novelty => novelty,
+            EvaluationMode::Blended(weight) => {
+                // Both scores are normalized to [0, 1] before blending
+                let max_novelty = novelty.iter().cloned().fold(f32::EPSILON, f32::max);
+                fitness
+                    .iter()
+                    .zip(&novelty)
+                    .map(|(f, n)| (1.0 - weight) * f / max_fitness + weight * n / max_novelty)
+                    .collect()
+            }
+        };
+
+        // Rockets in crowded species get a smaller share of the parents
+        let sharing = self.params.sharing;
+        let mut species_count = 0;
+        if sharing.enabled {
+            let (species, count) =
+                species::speciate(&self.agents, sharing.metric, sharing.threshold);
+            species::share_fitness(&mut weights, &species, count);
+            species_count = count;
+        }
+
+        (weights, species_count)
+    }
+
+    /// Rank the rockets with non-dominated sorting and crowding distance over their objectives
+    ///
+    /// Survival is elitist (μ+λ), the rockets compete with their parents
+    /// and the best ranked of both replace the rockets, as parents of the next generation
+    ///
+    /// Scores are the positions in the crowded comparison, the best rocket scoring the highest,
+    /// so that within a front the less crowded rocket wins a tournament
+    fn ranked_scores(&mut self) -> Vec<f32> {
+        let size = self.agents.len();
+        let mut candidates = std::mem::take(&mut self.agents);
+        candidates.append(&mut self.parents);
+
+        let lifespan = self.params.lifespan;
+        let objectives = parallel::map(&candidates, |r| r.objectives(lifespan));
+        let ranks = nsga2::non_dominated_sort(&objectives);
+        let crowding = nsga2::crowding_distance(&objectives, &ranks);
+
+        self.pareto_front = objectives
+            .iter()
+            .zip(&ranks)
+            .filter(|&(_, &rank)| rank == 0)
+            .map(|(o, _)| *o)
+            .collect();
+
+        // Whole fronts survive in order of rank, the last one partially by crowding distance
+        let mut ranked: Vec<_> = candidates
+            .into_iter()
+            .zip(ranks.into_iter().zip(crowding))
+            .collect();
+        ranked.sort_by(|a, b| nsga2::crowded_compare(a.1, b.1));
+        ranked.truncate(size);
+
+        let survivors: Vec<_> = ranked.into_iter().map(|(rocket, _)| rocket).collect();
+        self.parents = survivors.clone();
+        self.agents = survivors;
+
+        (0..size).map(|i| (size - i) as f32).collect()
+    }
+
+    fn draw_trail(draw: &Draw, trail: &[Vec2], color: Rgb<u8>) {
+        // Nothing to draw until the trail has at least one segment
+        if trail.len() < 2 {
+            return;
+        }
+
+        draw.polyline()
+            .weight(TRAIL_WEIGHT)
+            .points(trail.iter().cloned())
+            .color(color);
     }
 }
 
@@ -46,13 +366,10 @@ impl Population {
 /// Or refer the link below for more info on evolutionary algorithms,
 /// https://natureofcode.com/book/chapter-9-the-evolution-of-code/
 impl Evolution for Population {
-    /// Create the initial set of rockets to begin the simulation
-    fn initialize() -> Self {
-        let rockets: Vec<Rocket> = (0..NUM_ROCKETS).map(|_| Rocket::new(None)).collect();
-        Population {
-            agents: rockets,
-            gene_pool: None,
-        }
+    /// Create the initial set of `size` rockets to begin the simulation
+    /// All randomness in the population is seeded with `seed`
+    fn initialize(size: usize, seed: u64) -> Self {
+        Population::with_params(size, seed, EvolutionParams::default())
     }
 
     /// Calculate the quality of the population,
@@ -61,52 +378,156 @@ impl Evolution for Population {
     /// wrt the problem under consideration
     fn selection(&mut self) {
         let mut max_fitness = 0.0;
-        let mut weights = Vec::new();
+        let mut fittest = 0;
 
         // Calculate fitness for every rocket
-        for v in &mut self.agents {
-            let fitness = v.fitness();
-            weights.push(fitness);
-
-            if fitness > max_fitness {
-                max_fitness = fitness;
+        let fitness = parallel::map(&self.agents, |v| v.fitness());
+        for (i, &f) in fitness.iter().enumerate() {
+            if f > max_fitness {
+                max_fitness = f;
+                fittest = i;
             }
         }
 
-        // Normalize fitness,
-        // i.e map the fitness to a value between 0 and 100,
-        // this helps avoid values being too large or too small
-        weights
-            .iter_mut()
-            .for_each(|i| *i = (*i / max_fitness) * 100.0);
+        // Remember the trajectories of the fittest rockets
+        let best_trail = self.agents[fittest].path().to_vec();
+        match &self.ghost {
+            Some((ghost_fitness, _)) if *ghost_fitness >= max_fitness => (),
+            _ => self.ghost = Some((max_fitness, best_trail.clone())),
+        }
+        self.best_trail = best_trail;
 
-        self.gene_pool = Some(WeightedIndex::new(&weights)
-            .expect("Failed to generate gene pool"));
+        // Parents are picked in proportion to their score,
+        // or by a binary tournament of the crowded comparison under NSGA-II
+        self.scores = Some(match self.params.selection_method {
+            SelectionMethod::FitnessProportionate => {
+                self.parents.clear();
+                self.engine.set_selection(RouletteWheel);
+                let novelty = match self.params.evaluation_mode {
+                    EvaluationMode::Objective => Vec::new(),
+                    _ => {
+                        let novelty = self.params.novelty;
+                        self.novelty_archive.score(
+                            &self.behaviours(),
+                            novelty.nearest_neighbours,
+                            novelty.archive_threshold,
+                        )
+                    }
+                };
+                let (scores, species_count) = self.weighted_scores(fitness, novelty);
+                self.species_count = species_count;
+                scores
+            }
+            SelectionMethod::Nsga2 => {
+                self.engine.set_selection(Tournament(2));
+                self.ranked_scores()
+            }
+        });
     }
 
     /// Use the results from `selection` and generate new rockets for the next generation
     fn reproduction(&mut self) {
-        let mut rng = thread_rng();
-        let mut new_population = Vec::new();
-
-        match &self.gene_pool {
-            Some(genes) => {
-                for _ in 0..NUM_ROCKETS {
-                    // Select two rockets randomly
-                    // The probability of picking a rocket is proportional to its fitness
-                    let first = self.agents[genes.sample(&mut rng)].clone();
-                    let second = self.agents[genes.sample(&mut rng)].clone();
-                    let child = Rocket::reproduce(&first, &second);
-                    new_population.push(child);
-                }
+        // Not evaluated,
+        // new rockets can't be generated
+        let Some(scores) = self.scores.take() else {
+            return;
+        };
 
-                self.agents.clear();
-                self.agents = new_population;
-            }
+        // Migrants and survivors have replaced some of the rockets since they were bred
+        let genomes = self.agents.iter().map(|r| r.dna().clone()).collect();
+        self.engine.set_genomes(genomes);
+        self.engine.breed(&scores);
+        self.agents = parallel::map(self.engine.genomes(), |dna| Rocket::new(Some(dna)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{MapGenerator, MapKind};
 
-            // No gene pool,
-            // new rockets can't be generated
-            None => return,
+    /// Fly the rockets of `population` through `world` for `frames` frames
+    fn fly(population: &mut Population, world: &World, frames: usize) {
+        let mut heatmap = Heatmap::new(world.grid_size());
+        population.reset(world);
+        (1..=frames).for_each(|frame| {
+            population.update(frame, world, &mut heatmap);
+        });
+    }
+
+    fn objectives(population: &Population) -> Vec<[f32; 3]> {
+        population
+            .rockets()
+            .iter()
+            .map(|r| r.objectives(population.params.lifespan))
+            .collect()
+    }
+
+    #[test]
+    fn nsga2_survivors_are_the_best_of_parents_and_children() {
+        let world = MapGenerator::new(42).generate(MapKind::Obstacles);
+        let mut population = Population::initialize(20, 42);
+        population.set_selection_method(SelectionMethod::Nsga2);
+        fly(&mut population, &world, 40);
+        population.selection();
+        let parents = objectives(&population);
+
+        population.reproduction();
+        fly(&mut population, &world, 20);
+        let children = objectives(&population);
+        population.selection();
+        let survivors = objectives(&population);
+        assert_eq!(survivors.len(), 20);
+
+        // The Pareto front is taken over parents and children, and survives whole
+        let merged: Vec<_> = children.iter().chain(&parents).cloned().collect();
+        let ranks = nsga2::non_dominated_sort(&merged);
+        let front: Vec<_> = (0..merged.len())
+            .filter(|&i| ranks[i] == 0)
+            .map(|i| merged[i])
+            .collect();
+        assert_eq!(population.pareto_front(), front.as_slice());
+        assert!(front.len() <= 20);
+        assert!(front.iter().all(|o| survivors.contains(o)));
+
+        // No survivor is ranked worse than a rocket that didn't survive
+        let worst = survivors
+            .iter()
+            .map(|o| ranks[merged.iter().position(|m| m == o).unwrap()])
+            .max()
+            .unwrap();
+        let survivor_ranks = (0..merged.len()).filter(|&i| ranks[i] < worst).count();
+        assert!(survivor_ranks <= 20);
+    }
+
+    #[test]
+    fn less_crowded_rockets_win_nsga2_tournaments() {
+        let world = MapGenerator::new(42).generate(MapKind::Obstacles);
+        let mut population = Population::initialize(20, 42);
+        population.set_selection_method(SelectionMethod::Nsga2);
+        fly(&mut population, &world, 40);
+
+        // Without parents, the rockets only compete with each other
+        let candidates = objectives(&population);
+        let ranks = nsga2::non_dominated_sort(&candidates);
+        let crowding = nsga2::crowding_distance(&candidates, &ranks);
+        population.selection();
+        let scores = population.scores.clone().unwrap();
+
+        // A tournament is won by the highest score
+        let survivors: Vec<usize> = objectives(&population)
+            .iter()
+            .map(|o| candidates.iter().position(|c| c == o).unwrap())
+            .collect();
+        let mut compared = 0;
+        for (a, &i) in survivors.iter().enumerate() {
+            for (b, &j) in survivors.iter().enumerate() {
+                if ranks[i] == ranks[j] && crowding[i] > crowding[j] {
+                    assert!(scores[a] > scores[b]);
+                    compared += 1;
+                }
+            }
         }
+        assert!(compared > 0);
     }
 }
@@ -0,0 +1,286 @@
+//! Replays of past generations
+//!
+//! A generation is recorded as the DNA of its rockets, which is all it takes to fly it again,
+//! since rockets don't interact with each other and the world is deterministic
+//!
+//! A `Replay` flies a recorded generation once, then plays it back from any frame,
+//! forwards or backwards and at varying speeds
+//!
+//! Generations are replayed through the current world,
+//! so edits made to the map since a generation ran change its replay
+
+use std::collections::VecDeque;
+
+use crate::genetics::Dna;
+use crate::parallel;
+use crate::rocket::{Rocket, RocketState};
+use crate::world::World;
+use crate::{REPLAY_MAX_SPEED, REPLAY_MIN_SPEED};
+use nannou::glam::Vec2;
+use nannou::Draw;
+
+/// The DNA of the rockets of the last `capacity` generations
+pub struct Recording {
+    generations: VecDeque<(u32, Vec<Dna>)>,
+    capacity: usize,
+}
+
+impl Recording {
+    pub fn new(capacity: usize) -> Self {
+        Recording {
+            generations: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Record the DNA of the rockets of `generation`,
+    /// dropping the oldest generation once `capacity` generations are recorded
+    pub fn record(&mut self, generation: u32, genomes: Vec<Dna>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.generations.len() == self.capacity {
+            self.generations.pop_front();
+        }
+        self.generations.push_back((generation, genomes));
+    }
+
+    /// DNA of the rockets of `generation`, `None` if it isn't recorded
+    pub fn get(&self, generation: u32) -> Option<&[Dna]> {
+        self.generations
+            .iter()
+            .find(|(g, _)| *g == generation)
+            .map(|(_, genomes)| genomes.as_slice())
+    }
+
+    /// Oldest and latest generations recorded
+    pub fn range(&self) -> Option<(u32, u32)> {
+        Some((self.generations.front()?.0, self.generations.back()?.0))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.generations.is_empty()
+    }
+
+    /// Number of generations kept, 0 if nothing is recorded
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Flight of a single rocket during a replayed generation
+struct Flight {
+    /// Position and velocity of the rocket after every frame, starting from the spawn
+    /// Ends at the frame the rocket crashed or reached the target
+    path: Vec<(Vec2, Vec2)>,
+    /// State of the rocket at the end of its path
+    state: RocketState,
+}
+
+impl Flight {
+    /// Position, velocity and state of the rocket after `frame` frames
+    fn at(&self, frame: usize) -> (Vec2, Vec2, RocketState) {
+        let last = self.path.len() - 1;
+        let (pos, vel) = self.path[frame.min(last)];
+        let state = match frame >= last {
+            true => self.state,
+            false => RocketState::Alive,
+        };
+        (pos, vel, state)
+    }
+}
+
+/// Playback of a recorded generation
+pub struct Replay {
+    generation: u32,
+    flights: Vec<Flight>,
+    /// Frames of the replayed generation
+    lifespan: usize,
+    /// Frames played so far, fractional at speeds below 1
+    frame: f32,
+    /// Frames played per update, negative when playing backwards
+    speed: f32,
+    playing: bool,
+}
+
+impl Replay {
+    /// Fly the rockets of `generation` with `genomes` through `world`
+    /// for a generation of `lifespan` frames, the replay starts paused at the first frame
+    pub fn new(generation: u32, genomes: &[Dna], world: &World, lifespan: usize) -> Self {
+        Replay {
+            generation,
+            flights: Replay::fly(genomes, world, lifespan),
+            lifespan,
+            frame: 0.0,
+            speed: 1.0,
+            playing: false,
+        }
+    }
+
+    /// Replay `generation` instead, keeping the frame, speed and direction of the playback
+    pub fn set_generation(&mut self, generation: u32, genomes: &[Dna], world: &World) {
+        self.generation = generation;
+        self.flights = Replay::fly(genomes, world, self.lifespan);
+    }
+
+    /// Advance the playback by an update, pausing once either end is reached
+    pub fn update(&mut self) {
+        if !self.playing {
+            return;
+        }
+
+        let frame = self.frame + self.speed;
+        self.frame = frame.clamp(0.0, self.lifespan as f32);
+        if self.frame != frame {
+            self.playing = false;
+        }
+    }
+
+    /// Draw `world` and the rockets as they were at the current frame
+    pub fn draw(&self, draw: &Draw, world: &World) {
+        let frame = self.frame();
+        world.draw(draw);
+        world.draw_obstacles(draw, frame % self.lifespan);
+        for flight in &self.flights {
+            let (pos, vel, state) = flight.at(frame);
+            Rocket::draw_at(draw, pos, vel, state);
+        }
+    }
+
+    /// Position and state of every rocket at the current frame
+    pub fn rockets(&self) -> impl Iterator<Item = (Vec2, RocketState)> + '_ {
+        let frame = self.frame();
+        self.flights.iter().map(move |flight| {
+            let (pos, _, state) = flight.at(frame);
+            (pos, state)
+        })
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Frames played so far, between 0 and `lifespan`
+    pub fn frame(&self) -> usize {
+        self.frame as usize
+    }
+
+    /// Frames of the replayed generation
+    pub fn lifespan(&self) -> usize {
+        self.lifespan
+    }
+
+    /// Jump to `frame`, clamped to the frames of the generation
+    pub fn seek(&mut self, frame: usize) {
+        self.frame = frame.min(self.lifespan) as f32;
+    }
+
+    /// Pause and move by `frames` frames, backwards if negative
+    pub fn step(&mut self, frames: isize) {
+        self.playing = false;
+        self.seek(self.frame().saturating_add_signed(frames));
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Play forwards, or backwards if `backwards`, restarting from the end that was reached
+    pub fn play(&mut self, backwards: bool) {
+        self.speed = match backwards {
+            true => -self.speed.abs(),
+            false => self.speed.abs(),
+        };
+        match (backwards, self.frame()) {
+            (false, frame) if frame == self.lifespan => self.frame = 0.0,
+            (true, 0) => self.frame = self.lifespan as f32,
+            _ => (),
+        }
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Frames played per update, negative when playing backwards
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Multiply the playback speed by `factor`,
+    /// within `REPLAY_MIN_SPEED` and `REPLAY_MAX_SPEED` frames per update
+    pub fn scale_speed(&mut self, factor: f32) {
+        let speed = (self.speed.abs() * factor).clamp(REPLAY_MIN_SPEED, REPLAY_MAX_SPEED);
+        self.speed = speed.copysign(self.speed);
+    }
+
+    /// Fly a rocket per genome through `world` for a whole generation of `lifespan` frames
+    fn fly(genomes: &[Dna], world: &World, lifespan: usize) -> Vec<Flight> {
+        parallel::map(genomes, |dna| {
+            let mut path = vec![(world.spawn(), Vec2::ZERO)];
+            let rocket = Rocket::launch(dna, world, lifespan, |r| path.push((r.pos, r.velocity())));
+            Flight {
+                path,
+                state: rocket.state(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{MapGenerator, MapKind};
+    use crate::simulation::{EvolutionParams, Simulation};
+
+    #[test]
+    fn replay_matches_the_live_generation() {
+        let world = MapGenerator::new(42).generate(MapKind::Corridors);
+        let params = EvolutionParams {
+            num_rockets: 50,
+            ..EvolutionParams::default()
+        };
+        let mut simulation = Simulation::with_params(world, 42, params);
+        simulation.set_recording(2);
+        simulation.run_generations(2);
+
+        // Position and state of every rocket after every frame of the next generation
+        let mut live = Vec::new();
+        loop {
+            simulation.update();
+            live.push(
+                simulation
+                    .rockets()
+                    .map(|r| (r.pos, r.state()))
+                    .collect::<Vec<_>>(),
+            );
+            if simulation.frame_idx() == 0 {
+                break;
+            }
+        }
+
+        let (_, generation) = simulation.recording().range().unwrap();
+        let genomes = simulation.recording().get(generation).unwrap();
+        let lifespan = simulation.params().lifespan;
+        let mut replay = Replay::new(generation, genomes, simulation.world(), lifespan);
+        for (frame, rockets) in live.iter().enumerate() {
+            replay.seek(frame + 1);
+            assert_eq!(
+                &replay.rockets().collect::<Vec<_>>(),
+                rockets,
+                "frame {}",
+                frame + 1
+            );
+        }
+
+        // Generations end early once every rocket stopped
+        let ended = live.last().unwrap();
+        replay.seek(lifespan);
+        assert_eq!(&replay.rockets().collect::<Vec<_>>(), ended);
+        assert!(ended
+            .iter()
+            .any(|(_, state)| *state == RocketState::Crashed));
+    }
+}
@@ -1,16 +1,21 @@
 //! Rocket - The Simulation Agent
 
 use crate::{
-    ROCKET_COLOR, ROCKET_COLOR_COMPLETED, ROCKET_COLOR_CRASHED, ROCKET_SIZE, ROCKET_SPAWN_LOCATION,
-    ROCKET_STROKE_COLOR, TARGET_LOCATION, TARGET_RADIUS,
+    CHECKPOINT_BONUS, ROCKET_COLOR, ROCKET_COLOR_COMPLETED, ROCKET_COLOR_CRASHED,
+    ROCKET_RECORD_PATH, ROCKET_SIZE, ROCKET_SPAWN_LOCATION, ROCKET_STROKE_COLOR, TARGET_LOCATION,
+    TARGET_RADIUS,
 };
 use nannou::glam::{vec2, Vec2};
 use nannou::prelude::*;
 use nannou::Draw;
+use rand::Rng;
 
-use crate::genetics::{Dna, Reproduction};
+use crate::genetics::{Dna, MutationParams, Reproduction};
+use crate::species::DistanceMetric;
+use crate::world::{Wall, World};
 
-#[derive(Clone)]
+/// State of a rocket within its generation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RocketState {
     Alive,     // Can move around
     Crashed,   // Collided with a wall
@@ -29,6 +34,8 @@ pub enum RocketState {
 pub struct Rocket {
     /// The world position of the rocket
     pub pos: Vec2,
+    /// The world position of the rocket before its last move
+    prev_pos: Vec2,
     /// Velocity
     /// A vector representing the speed and direction of motion
     vel: Vec2,
@@ -36,33 +43,75 @@ pub struct Rocket {
     /// Force applied to the rocket every frame
     /// Or rate of change of velocity per frame
     acc: Vec2,
+    /// The world positions the rocket has to reach in order,
+    /// checkpoints followed by the target
+    course: Vec<Vec2>,
+    /// Index into `course` of the next position to reach
+    checkpoint: usize,
     /// Current state of the rocket
     state: RocketState,
     /// Genetic information related to the rocket
     dna: Dna,
+    /// Every position the rocket has been at, starting from the spawn
+    /// Only recorded when `ROCKET_RECORD_PATH` is enabled
+    path: Vec<Vec2>,
+    /// Total magnitude of the forces applied by the rocket
+    fuel: f32,
+    /// Number of frames the rocket has been flying for
+    frames: usize,
 }
 
 impl Rocket {
     pub fn new(dna: Option<&Dna>) -> Self {
         Self {
             pos: vec2(ROCKET_SPAWN_LOCATION.0, ROCKET_SPAWN_LOCATION.1),
+            prev_pos: vec2(ROCKET_SPAWN_LOCATION.0, ROCKET_SPAWN_LOCATION.1),
             vel: Vec2::ZERO,
             acc: Vec2::ZERO,
+            course: vec![vec2(TARGET_LOCATION.0, TARGET_LOCATION.1)],
+            checkpoint: 0,
             state: RocketState::Alive,
             // Use the `dna` if provided, else randomize it
             dna: dna.unwrap_or(&Dna::new(None)).clone(),
+            path: Vec::new(),
+            fuel: 0.0,
+            frames: 0,
         }
     }
 
-    pub fn update(&mut self, frame_idx: usize, is_wall: bool) {
+    /// Place the rocket at `spawn`, at rest and alive,
+    /// and point it at the first position of `course`
+    pub fn reset(&mut self, spawn: Vec2, course: &[Vec2]) {
+        self.pos = spawn;
+        self.prev_pos = spawn;
+        self.vel = Vec2::ZERO;
+        self.acc = Vec2::ZERO;
+        self.state = RocketState::Alive;
+        self.course = course.to_vec();
+        self.checkpoint = 0;
+        self.fuel = 0.0;
+        self.frames = 0;
+
+        self.path.clear();
+        if ROCKET_RECORD_PATH {
+            self.path.push(spawn);
+        }
+    }
+
+    /// Move the rocket by a frame, `external_force` is applied on top of its own thrust
+    /// e.g the force fields of the world
+    pub fn update(&mut self, frame_idx: usize, is_wall: bool, external_force: Vec2) {
         if let RocketState::Crashed | RocketState::Completed = self.state {
             return;
         }
 
-        // Target collision
+        // Checkpoint/target collision
         if self.target_distance() <= TARGET_RADIUS {
-            self.state = RocketState::Completed;
-            return;
+            self.checkpoint += 1;
+            if self.checkpoint >= self.course.len() {
+                self.state = RocketState::Completed;
+                return;
+            }
         }
 
         // Wall or boundary collision
@@ -72,24 +121,125 @@ impl Rocket {
         }
 
         // Update the position of the rocket based on its velocity
-        self.apply_force(&self.dna.get(frame_idx));
+        let force = self.dna.force(frame_idx);
+        self.apply_force(&(force + external_force));
+        self.fuel += force.length();
+        self.frames += 1;
+        if ROCKET_RECORD_PATH {
+            self.path.push(self.pos);
+        }
+    }
+
+    /// Move the rocket by a frame through `world`,
+    /// crashing into its walls and pushed by its forces
+    pub fn fly(&mut self, frame_idx: usize, world: &World) {
+        // Walls crossed during the last move are caught by the shapes' and obstacles'
+        // exact collision
+        let is_wall = world.is_wall_at(&self.pos, frame_idx)
+            || world.hits_shape(&self.prev_pos, &self.pos)
+            || world.hits_obstacle(&self.prev_pos, &self.pos, frame_idx);
+        self.update(frame_idx, is_wall, world.force_at(&self.pos));
+    }
+
+    /// Fly a rocket with `dna` from the `world` spawn for a whole generation of `lifespan` frames
+    /// on its own, `on_frame` is called after every frame until the rocket crashes
+    /// or reaches the target
+    ///
+    /// Frames are numbered as in `Simulation::update`, the last frame being 0
+    pub fn launch(
+        dna: &Dna,
+        world: &World,
+        lifespan: usize,
+        mut on_frame: impl FnMut(&Rocket),
+    ) -> Rocket {
+        let mut rocket = Rocket::new(Some(dna));
+        rocket.reset(world.spawn(), &world.course());
+        for frame in 1..=lifespan {
+            rocket.fly(frame % lifespan, world);
+            on_frame(&rocket);
+            if rocket.state != RocketState::Alive {
+                break;
+            }
+        }
+
+        rocket
     }
 
     pub fn draw(&self, draw: &Draw) {
+        Rocket::draw_at(draw, self.pos, self.vel, self.state);
+    }
+
+    /// Draw a rocket in `state` at `pos`, facing the direction of `vel`
+    pub fn draw_at(draw: &Draw, pos: Vec2, vel: Vec2, state: RocketState) {
         // Calculate the direction the rocket must face
-        let theta = self.vel.angle() + PI / 2.0;
+        let theta = vel.angle() + PI / 2.0;
         draw.rect()
-            .color(self.color())
+            .color(Rocket::color(state))
             .w_h(ROCKET_SIZE.0, ROCKET_SIZE.1)
-            .x_y(self.pos.x, self.pos.y)
+            .x_y(pos.x, pos.y)
             .stroke(ROCKET_STROKE_COLOR)
             .stroke_weight(0.5)
             .rotate(theta);
     }
 
-    /// Create a new child rocket from `first` and `second` parent rockets
-    pub fn reproduce(first: &Self, second: &Self) -> Self {
-        let child_dna = Dna::crossover(&first.dna, &second.dna).mutate();
+    /// The world position of the rocket before its last move
+    pub fn prev_pos(&self) -> Vec2 {
+        self.prev_pos
+    }
+
+    /// Distance moved in the last frame, in window coordinates
+    pub fn velocity(&self) -> Vec2 {
+        self.vel
+    }
+
+    pub fn state(&self) -> RocketState {
+        self.state
+    }
+
+    pub fn dna(&self) -> &Dna {
+        &self.dna
+    }
+
+    /// Trajectory of the rocket, empty unless `ROCKET_RECORD_PATH` is enabled
+    pub fn path(&self) -> &[Vec2] {
+        &self.path
+    }
+
+    /// Number of checkpoints of the course passed, including the target once reached
+    pub fn checkpoints_passed(&self) -> usize {
+        self.checkpoint
+    }
+
+    /// Distance to the `other` rocket as measured by `metric`
+    pub fn distance(&self, other: &Self, metric: DistanceMetric) -> f32 {
+        match metric {
+            DistanceMetric::Genes => self.dna.distance(&other.dna),
+            DistanceMetric::Trajectory => {
+                // A rocket that stopped early (crashed/completed) stays at its last position
+                let frames = self.path.len().max(other.path.len());
+                if frames == 0 {
+                    return self.pos.distance(other.pos);
+                }
+
+                let at =
+                    |path: &[Vec2], i: usize| *path.get(i).or(path.last()).unwrap_or(&Vec2::ZERO);
+                let total: f32 = (0..frames)
+                    .map(|i| at(&self.path, i).distance(at(&other.path, i)))
+                    .sum();
+                total / frames as f32
+            }
+        }
+    }
+
+    /// Create a new child rocket from `first` and `second` parent rockets,
+    /// its DNA mutated as defined by `mutation`
+    pub fn reproduce<R: Rng>(
+        first: &Self,
+        second: &Self,
+        mutation: MutationParams,
+        rng: &mut R,
+    ) -> Self {
+        let child_dna = Dna::crossover(&first.dna, &second.dna, rng).mutate(rng, mutation);
         Rocket::new(Some(&child_dna))
     }
 
@@ -100,27 +250,60 @@ impl Rocket {
     /// Fitness is inversely proportional to distance to the target
     /// i.e the closer the rocket is to the target, the larger is its fitness
     ///
+    /// With checkpoints, the distance is measured along the rest of the course,
+    /// and every checkpoint passed adds a `CHECKPOINT_BONUS`,
+    /// so a rocket that passed more checkpoints is always fitter
+    ///
     /// This approach doesn't yield accurate results for a complex world with walls
     /// close to the target, since collisions with walls
     /// near target will have a high fitness
     /// One approach to fix this is to use a Flood fill algorithm
-    pub fn fitness(&mut self) -> f32 {
-        // Division by zero leads to `inf`
-        let inv_target_dist = 1.0 / self.target_distance();
+    pub fn fitness(&self) -> f32 {
+        // Rockets within a checkpoint are as close as it gets,
+        // this bounds the distance term below the bonus and avoids division by zero
+        let inv_target_dist = 1.0 / self.course_distance().max(TARGET_RADIUS);
 
         // Optional step, amplifies the fitness value
-        inv_target_dist * inv_target_dist
+        let distance_fitness = inv_target_dist * inv_target_dist;
+
+        self.checkpoint as f32 * CHECKPOINT_BONUS + distance_fitness
+    }
+
+    /// Objectives for multi-objective optimization, all of which are minimized,
+    /// - Distance to the target, along the rest of the course
+    /// - Fuel used
+    /// - Frames taken to reach the target, the `lifespan` of a generation if it wasn't reached
+    pub fn objectives(&self, lifespan: usize) -> [f32; 3] {
+        let time = match self.state {
+            RocketState::Completed => self.frames,
+            _ => lifespan,
+        };
+        [self.course_distance(), self.fuel, time as f32]
     }
 
-    /// Return the distance between the rocket and the target
+    /// Return the distance between the rocket and the next checkpoint (or target)
+    /// Once the target is reached, the distance to the target
     fn target_distance(&self) -> f32 {
-        self.pos
-            .distance(vec2(TARGET_LOCATION.0, TARGET_LOCATION.1))
+        let next = self.checkpoint.min(self.course.len() - 1);
+        self.pos.distance(self.course[next])
+    }
+
+    /// Return the distance to the target along the rest of the course,
+    /// i.e through every checkpoint not yet passed
+    fn course_distance(&self) -> f32 {
+        let remaining: f32 = self
+            .course
+            .get(self.checkpoint..)
+            .unwrap_or(&[])
+            .windows(2)
+            .map(|w| w[0].distance(w[1]))
+            .sum();
+        self.target_distance() + remaining
     }
 
     /// Return a color based on the rocket state
-    fn color(&self) -> Rgb<u8> {
-        match self.state {
+    fn color(state: RocketState) -> Rgb<u8> {
+        match state {
             RocketState::Crashed => ROCKET_COLOR_CRASHED,
             RocketState::Completed => ROCKET_COLOR_COMPLETED,
             _ => ROCKET_COLOR,
@@ -133,7 +316,50 @@ impl Rocket {
     fn apply_force(&mut self, force: &Vec2) {
         self.acc += *force;
         self.vel += self.acc;
+        self.prev_pos = self.pos;
         self.pos += self.vel;
         self.acc = Vec2::ZERO;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn more_checkpoints_are_fitter() {
+        let course = [vec2(0.0, 0.0), vec2(500.0, 0.0), vec2(500.0, 500.0)];
+        let mut rocket = Rocket::new(None);
+        rocket.reset(vec2(-100.0, 0.0), &course);
+
+        // Right next to a checkpoint, and far from the next one after passing it
+        let mut fitness = Vec::new();
+        for (checkpoint, pos) in course.iter().enumerate() {
+            rocket.pos = *pos - vec2(TARGET_RADIUS + 1.0, 0.0);
+            rocket.checkpoint = checkpoint;
+            fitness.push(rocket.fitness());
+            rocket.pos = vec2(-1000.0, -1000.0);
+            rocket.checkpoint = checkpoint + 1;
+            fitness.push(rocket.fitness());
+        }
+        assert!(fitness.windows(2).all(|w| w[0] < w[1]), "{:?}", fitness);
+        assert!(fitness.iter().all(|f| f.is_finite()));
+    }
+
+    #[test]
+    fn reset_rockets_start_over_at_rest() {
+        let course = [vec2(500.0, 0.0)];
+        let mut rocket = Rocket::new(None);
+        rocket.reset(Vec2::ZERO, &course);
+        for frame in 1..10 {
+            rocket.update(frame, false, vec2(1.0, 0.0));
+        }
+        rocket.update(10, true, Vec2::ZERO);
+        assert_eq!(rocket.state(), RocketState::Crashed);
+
+        rocket.reset(vec2(-100.0, 0.0), &course);
+        assert_eq!(rocket.state(), RocketState::Alive);
+        assert_eq!((rocket.velocity(), rocket.acc), (Vec2::ZERO, Vec2::ZERO));
+        assert_eq!(rocket.prev_pos(), vec2(-100.0, 0.0));
+    }
+}
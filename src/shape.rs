@@ -0,0 +1,264 @@
+//! Vector geometry walls
+//!
+//! Polygons, circles and line segments that aren't bound to the grid,
+//! used to model curved and diagonal walls
+//!
+//! Collisions are exact, the movement of a rocket during a frame (a line segment)
+//! is tested against every shape, so rockets can't skip through thin walls
+//!
+//! Shapes are defined in the scene file (or the map file after the grid), one per line,
+//! positions and sizes are in window coordinates (pixels),
+//! - `polygon <x,y> <x,y> <x,y> ...`
+//! - `circle <x,y> <radius>`
+//! - `segment <x,y> <x,y>`
+
+use std::fmt;
+
+use crate::obstacle::parse_vec2;
+use crate::GRID_COLOR;
+use nannou::prelude::*;
+use nannou::Draw;
+
+/// A wall of arbitrary geometry
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// Closed polygon through the vertices, in order
+    Polygon(Vec<Vec2>),
+    Circle {
+        center: Vec2,
+        radius: f32,
+    },
+    /// Line segment, a wall with no thickness
+    Segment {
+        start: Vec2,
+        end: Vec2,
+    },
+}
+
+impl Shape {
+    /// Parse a shape definition line of the scene file,
+    /// returns `None` if `line` isn't a valid shape
+    pub fn parse(line: &str) -> Option<Shape> {
+        let mut tokens = line.split_whitespace();
+        let kind = tokens.next()?;
+        let args: Vec<&str> = tokens.collect();
+
+        let shape = match (kind, args.as_slice()) {
+            ("polygon", vertices) if vertices.len() >= 3 => Shape::Polygon(
+                vertices
+                    .iter()
+                    .map(|v| parse_vec2(v))
+                    .collect::<Option<_>>()?,
+            ),
+            ("circle", [center, radius]) => Shape::Circle {
+                center: parse_vec2(center)?,
+                radius: radius.parse().ok()?,
+            },
+            ("segment", [start, end]) => Shape::Segment {
+                start: parse_vec2(start)?,
+                end: parse_vec2(end)?,
+            },
+            _ => return None,
+        };
+
+        Some(shape)
+    }
+
+    /// Check if the window position `pos` lies inside the shape
+    /// Always `false` for line segments
+    pub fn contains(&self, pos: Vec2) -> bool {
+        match self {
+            Shape::Polygon(vertices) => {
+                // Even-odd rule, count the edges crossed by a ray towards +x
+                let mut inside = false;
+                for (a, b) in edges(vertices) {
+                    if (a.y > pos.y) != (b.y > pos.y) {
+                        let x = a.x + (pos.y - a.y) / (b.y - a.y) * (b.x - a.x);
+                        if pos.x < x {
+                            inside = !inside;
+                        }
+                    }
+                }
+                inside
+            }
+            Shape::Circle { center, radius } => pos.distance(*center) <= *radius,
+            Shape::Segment { .. } => false,
+        }
+    }
+
+    /// Check if the movement from `from` to `to` touches the shape
+    pub fn intersects(&self, from: Vec2, to: Vec2) -> bool {
+        match self {
+            Shape::Polygon(vertices) => {
+                self.contains(to)
+                    || edges(vertices).any(|(a, b)| segments_intersect(from, to, a, b))
+            }
+            Shape::Circle { center, radius } => segment_distance(*center, from, to) <= *radius,
+            Shape::Segment { start, end } => segments_intersect(from, to, *start, *end),
+        }
+    }
+
+    pub fn draw(&self, draw: &Draw) {
+        match self {
+            Shape::Polygon(vertices) => {
+                draw.polygon()
+                    .points(vertices.iter().cloned())
+                    .color(GRID_COLOR);
+            }
+            Shape::Circle { center, radius } => {
+                draw.ellipse()
+                    .x_y(center.x, center.y)
+                    .radius(*radius)
+                    .color(GRID_COLOR);
+            }
+            Shape::Segment { start, end } => {
+                draw.line()
+                    .start(*start)
+                    .end(*end)
+                    .weight(2.0)
+                    .color(GRID_COLOR);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    /// Format the shape as a scene file line, refer `Shape::parse`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Shape::Polygon(vertices) => {
+                write!(f, "polygon")?;
+                for v in vertices {
+                    write!(f, " {},{}", v.x, v.y)?;
+                }
+                Ok(())
+            }
+            Shape::Circle { center, radius } => {
+                write!(f, "circle {},{} {}", center.x, center.y, radius)
+            }
+            Shape::Segment { start, end } => {
+                write!(f, "segment {},{} {},{}", start.x, start.y, end.x, end.y)
+            }
+        }
+    }
+}
+
+/// Edges of a closed polygon, including the one from the last vertex back to the first
+fn edges(vertices: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(&a, &b)| (a, b))
+}
+
+/// Relative tolerance of the segment intersection test, a few times the `f32` precision
+const SEGMENT_TOLERANCE: f32 = 1e-6;
+
+/// Check if the segments `p1`-`p2` and `q1`-`q2` intersect, touching counts
+fn segments_intersect(p1: Vec2, p2: Vec2, q1: Vec2, q2: Vec2) -> bool {
+    // Side of the line `a`-`b` the point `c` lies on, 0 if collinear
+    let orientation = |a: Vec2, b: Vec2, c: Vec2| (b - a).perp_dot(c - a);
+    let (d1, d2) = (orientation(q1, q2, p1), orientation(q1, q2, p2));
+    let (d3, d4) = (orientation(p1, p2, q1), orientation(p1, p2, q2));
+
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+
+    // Touching or collinear and overlapping, an end point lies on the other segment
+    // Rounding errors grow with the coordinates, so the tolerance is relative to them
+    let scale = [p1, p2, q1, q2]
+        .iter()
+        .fold(1.0, |scale: f32, p| scale.max(p.abs().max_element()));
+    let tolerance = scale * SEGMENT_TOLERANCE;
+    let on_segment = |a: Vec2, b: Vec2, c: Vec2| segment_distance(c, a, b) <= tolerance;
+    on_segment(q1, q2, p1)
+        || on_segment(q1, q2, p2)
+        || on_segment(p1, p2, q1)
+        || on_segment(p1, p2, q2)
+}
+
+/// Distance from the point `p` to the segment `a`-`b`
+fn segment_distance(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = match ab.length_squared() {
+        len if len > 0.0 => ((p - a).dot(ab) / len).clamp(0.0, 1.0),
+        _ => 0.0,
+    };
+    p.distance(a + ab * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_intersect_crossing_touching_and_collinear() {
+        let (a, b) = (vec2(-10.0, 0.0), vec2(10.0, 0.0));
+        // Crossing
+        assert!(segments_intersect(a, b, vec2(0.0, -5.0), vec2(0.0, 5.0)));
+        // Missing, parallel and beyond the end
+        assert!(!segments_intersect(a, b, vec2(-10.0, 1.0), vec2(10.0, 1.0)));
+        assert!(!segments_intersect(a, b, vec2(11.0, -5.0), vec2(11.0, 5.0)));
+        // Touching at an end point
+        assert!(segments_intersect(a, b, vec2(10.0, 0.0), vec2(10.0, 5.0)));
+        assert!(segments_intersect(a, b, vec2(3.0, 0.0), vec2(3.0, 5.0)));
+        // Collinear, overlapping or not
+        assert!(segments_intersect(a, b, vec2(5.0, 0.0), vec2(20.0, 0.0)));
+        assert!(!segments_intersect(a, b, vec2(11.0, 0.0), vec2(20.0, 0.0)));
+    }
+
+    #[test]
+    fn segments_intersect_at_pixel_scale() {
+        // A move ending exactly on a diagonal wall far from the origin
+        let (start, end) = (vec2(300.0, 300.0), vec2(350.1, 349.7));
+        let on_wall = start.lerp(end, 0.3);
+        assert!(segments_intersect(vec2(310.0, 250.0), on_wall, start, end));
+        assert!(!segments_intersect(
+            vec2(310.0, 250.0),
+            on_wall - vec2(0.0, 0.01),
+            start,
+            end
+        ));
+    }
+
+    #[test]
+    fn polygon_contains_with_concave_vertices() {
+        // U shape, open at the top
+        let u = Shape::Polygon(vec![
+            vec2(0.0, 0.0),
+            vec2(30.0, 0.0),
+            vec2(30.0, 30.0),
+            vec2(20.0, 30.0),
+            vec2(20.0, 10.0),
+            vec2(10.0, 10.0),
+            vec2(10.0, 30.0),
+            vec2(0.0, 30.0),
+        ]);
+        assert!(u.contains(vec2(5.0, 20.0)));
+        assert!(u.contains(vec2(15.0, 5.0)));
+        assert!(!u.contains(vec2(15.0, 20.0)));
+        assert!(!u.contains(vec2(-1.0, 5.0)));
+    }
+
+    #[test]
+    fn circle_and_segment_block_fast_moves() {
+        let circle = Shape::Circle {
+            center: vec2(0.0, 0.0),
+            radius: 5.0,
+        };
+        assert!(circle.contains(vec2(3.0, 4.0)));
+        assert!(!circle.contains(vec2(4.0, 4.0)));
+        // Passing through the circle within a single frame
+        assert!(circle.intersects(vec2(-20.0, 1.0), vec2(20.0, 1.0)));
+        assert!(!circle.intersects(vec2(-20.0, 6.0), vec2(20.0, 6.0)));
+
+        let wall = Shape::Segment {
+            start: vec2(0.0, -10.0),
+            end: vec2(0.0, 10.0),
+        };
+        assert!(!wall.contains(vec2(0.0, 0.0)));
+        assert!(wall.intersects(vec2(-1.0, 0.0), vec2(1.0, 0.0)));
+        assert!(!wall.intersects(vec2(1.0, 0.0), vec2(2.0, 0.0)));
+    }
+}
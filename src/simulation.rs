@@ -1,12 +1,83 @@
 //! Simulation Controller
 
-use crate::genetics::Evolution;
-use crate::ROCKET_LIFESPAN;
+use crate::genetics::{Dna, Evolution, GenomeParams, MutationParams};
+use crate::{
+    EVALUATION_MODE, NUM_ISLANDS, NUM_ROCKETS, ROCKET_LIFESPAN, SELECTION_METHOD, SIMULATION_SEED,
+};
 use nannou::Draw;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::io;
+use std::time::Instant;
 
-use crate::population::Population;
+use crate::experiment::Experiment;
+use crate::heatmap::{Heatmap, HeatmapLayer};
+use crate::island::{self, MigrationParams};
+use crate::novelty::{EvaluationMode, NoveltyParams};
+use crate::nsga2;
+use crate::observer::{RocketEvent, SimulationObserver};
+use crate::population::{Population, SelectionMethod};
+use crate::replay::Recording;
+use crate::rocket::{Rocket, RocketState};
+use crate::species::SharingParams;
+use crate::stopping::{RunProgress, RunSummary, StoppingCriteria};
 use crate::world::World;
 
+/// Parameters of the evolution that can differ between simulations
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvolutionParams {
+    /// Number of rockets across all islands
+    pub num_rockets: usize,
+    pub num_islands: usize,
+    pub mutation: MutationParams,
+    pub selection_method: SelectionMethod,
+    /// Frames a generation lasts for at most
+    pub lifespan: usize,
+    pub genome: GenomeParams,
+    pub evaluation_mode: EvaluationMode,
+    pub novelty: NoveltyParams,
+    pub sharing: SharingParams,
+    pub migration: MigrationParams,
+}
+
+impl Default for EvolutionParams {
+    /// The configs, i.e `NUM_ROCKETS`, `NUM_ISLANDS`, `MUTATION_*`, `SELECTION_METHOD`,
+    /// `ROCKET_LIFESPAN`, the genome, `EVALUATION_MODE`, `NOVELTY_*`, speciation
+    /// and `MIGRATION_*` configs
+    fn default() -> Self {
+        EvolutionParams {
+            num_rockets: NUM_ROCKETS as usize,
+            num_islands: NUM_ISLANDS,
+            mutation: MutationParams::default(),
+            selection_method: SELECTION_METHOD,
+            lifespan: ROCKET_LIFESPAN,
+            genome: GenomeParams::default(),
+            evaluation_mode: EVALUATION_MODE,
+            novelty: NoveltyParams::default(),
+            sharing: SharingParams::default(),
+            migration: MigrationParams::default(),
+        }
+    }
+}
+
+/// Snapshot of the progress of a simulation, refer `Simulation::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SimulationStats {
+    pub generation: u32,
+    pub frame: usize,
+    /// Number of rockets in every state, across all islands
+    pub alive: usize,
+    pub crashed: usize,
+    pub completed: usize,
+    pub success_rate: f32,
+    pub best_fitness: f32,
+    pub mean_fitness: f32,
+    pub species_count: usize,
+    pub mean_genome_length: f32,
+    pub frames_run: u64,
+    pub frames_saved: u64,
+}
+
 /// Simulation State
 /// `model` for nannou application
 pub struct Simulation {
@@ -17,27 +88,115 @@ pub struct Simulation {
     /// Current frame index, updated every frame
     /// Defines how long a generation lasts
     frame_idx: usize,
-    /// `Population` of rockets
-    population: Population,
+    /// Sub-populations of rockets (islands) evolving independently,
+    /// except for the rockets migrating between them every few generations
+    ///
+    /// There is a single island unless `NUM_ISLANDS` is set
+    islands: Vec<Population>,
+    /// Parameters the islands evolve with, refer `with_params`
+    params: EvolutionParams,
+    /// Seed of all randomness of the simulation
+    seed: u64,
     /// Simulation environment i.e the `World`
     world: World,
+    /// Where rockets fly and crash in the `world`
+    heatmap: Heatmap,
+    /// Frames simulated over all generations
+    frames_run: u64,
+    /// Frames skipped over all generations by ending them early,
+    /// once no rocket was alive
+    frames_saved: u64,
+    /// Genomes replacing rockets of the next generation, refer `inject_genomes`
+    injected: Vec<Dna>,
+    /// Notified of the events of the simulation, refer `add_observer`
+    observers: Vec<Box<dyn SimulationObserver>>,
+    /// Fitness of the fittest rocket across all generations
+    all_time_best: f32,
+    /// DNA of the rockets of the last generations, refer `set_recording` and `Replay`
+    recording: Recording,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation::new()
+    }
 }
 
 impl Simulation {
     pub fn new() -> Self {
+        Simulation::with_world(match World::new() {
+            Ok(w) => w,
+            Err(_) => panic!("Error trying to create world"),
+        })
+    }
+
+    /// Create the simulation described by the experiment file located at `path`,
+    /// refer `Experiment`
+    pub fn load_experiment(path: &str) -> io::Result<Self> {
+        Experiment::load(path)?.simulation()
+    }
+
+    /// Create a simulation that takes place in `world`
+    /// Seeded with `SIMULATION_SEED`, or a random seed if it isn't set
+    pub fn with_world(world: World) -> Self {
+        Simulation::with_seed(world, SIMULATION_SEED.unwrap_or_else(random))
+    }
+
+    /// Create a simulation that takes place in `world`, with all randomness seeded by `seed`
+    pub fn with_seed(world: World, seed: u64) -> Self {
+        Simulation::with_params(world, seed, EvolutionParams::default())
+    }
+
+    /// Create a simulation that takes place in `world`, with all randomness seeded by `seed`,
+    /// evolving as defined by `params`
+    ///
+    /// The rockets are spread as evenly as possible across the islands,
+    /// there are at least a rocket and at most as many islands as rockets,
+    /// and generations last for at least a frame
+    pub fn with_params(world: World, seed: u64, mut params: EvolutionParams) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        params.num_rockets = params.num_rockets.max(1);
+        params.num_islands = params.num_islands.clamp(1, params.num_rockets);
+        params.lifespan = params.lifespan.max(1);
+        let (num_rockets, num_islands) = (params.num_rockets, params.num_islands);
+        let islands = (0..num_islands)
+            .map(|i| {
+                // The first islands take a rocket each of the remainder
+                let island_size =
+                    num_rockets / num_islands + usize::from(i < num_rockets % num_islands);
+                Population::with_params(island_size, rng.gen(), params)
+            })
+            .collect();
+
         Simulation {
             generation_count: 0,
             frame_idx: 0,
-            population: Population::new(),
-            world: match World::new() {
-                Ok(w) => w,
-                Err(_) => panic!("Error trying to create world"),
-            },
+            islands,
+            params,
+            seed,
+            heatmap: Heatmap::new(world.grid_size()),
+            world,
+            frames_run: 0,
+            frames_saved: 0,
+            injected: Vec::new(),
+            observers: Vec::new(),
+            all_time_best: 0.0,
+            recording: Recording::new(0),
         }
     }
 
-    /// Update the simulation
-    /// Every generation lasts for `ROCKET_LIFESPAN` frames
+    /// Restart the simulation from the first generation in `world`,
+    /// with the same seed, params, observers and recording capacity
+    pub fn set_world(&mut self, world: World) {
+        let mut simulation = Simulation::with_params(world, self.seed, self.params);
+        simulation.observers = std::mem::take(&mut self.observers);
+        simulation.set_recording(self.recording.capacity());
+        *self = simulation;
+    }
+
+    /// Update the simulation by a single frame
+    /// Every generation lasts for the `lifespan` of the params,
+    /// or ends early once every rocket has crashed or reached the target
     /// Then a new generation begins
     pub fn update(&mut self) {
         if self.frame_idx == 0 {
@@ -45,19 +204,328 @@ impl Simulation {
             self.start_new_generation();
         }
 
-        self.frame_idx = (self.frame_idx + 1) % ROCKET_LIFESPAN;
-        self.population.update(self.frame_idx, &self.world);
+        self.frame_idx = (self.frame_idx + 1) % self.params.lifespan;
+        for (i, island) in self.islands.iter_mut().enumerate() {
+            let finished = island.update(self.frame_idx, &self.world, &mut self.heatmap);
+            for idx in finished {
+                let event = RocketEvent {
+                    generation: self.generation_count,
+                    frame_idx: self.frame_idx,
+                    island: i,
+                    rocket: &island.rockets()[idx],
+                };
+                for observer in &mut self.observers {
+                    match event.rocket.state() {
+                        RocketState::Crashed => observer.on_rocket_crashed(&event),
+                        _ => observer.on_rocket_completed(&event),
+                    }
+                }
+            }
+        }
+        self.frames_run += 1;
+
+        // The remaining frames wouldn't change anything
+        if self.frame_idx != 0 && self.islands.iter().all(|p| p.is_finished()) {
+            self.frames_saved += (self.params.lifespan - self.frame_idx) as u64;
+            self.frame_idx = 0;
+        }
+
+        if self.frame_idx == 0 {
+            self.notify_generation_end();
+        }
+    }
+
+    /// Run the simulation until the current generation ends
+    /// If called at the end of a generation, runs the next generation entirely
+    pub fn run_generation(&mut self) {
+        loop {
+            self.update();
+            if self.frame_idx == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Run `generations` generations, the first one from the current frame onwards
+    pub fn run_generations(&mut self, generations: u32) {
+        for _ in 0..generations {
+            self.run_generation();
+        }
+    }
+
+    /// Run up to `generations` whole generations in a single call,
+    /// without them being rendered to the window
+    ///
+    /// Every `render_interval`th generation isn't skipped,
+    /// instead it's advanced by a single frame per call so that it can be watched
+    pub fn fast_forward(&mut self, generations: u32, render_interval: u32) {
+        for _ in 0..generations {
+            if self.is_snapshot_generation(render_interval) {
+                self.update();
+                return;
+            }
+
+            self.run_generation();
+        }
+    }
+
+    /// Run generations until one of the `criteria` is met,
+    /// without rendering them
+    ///
+    /// Never returns if `criteria` has no criterion set
+    pub fn run(&mut self, criteria: &StoppingCriteria) -> RunSummary {
+        let start = Instant::now();
+        let mut progress = RunProgress::default();
+        loop {
+            self.run_generation();
+            progress.record(
+                self.success_rate(),
+                self.best_fitness(),
+                criteria.plateau_tolerance,
+                start.elapsed(),
+            );
+
+            if let Some(reason) = criteria.check(&progress) {
+                return RunSummary {
+                    reason,
+                    progress,
+                    frames_run: self.frames_run,
+                    frames_saved: self.frames_saved,
+                };
+            }
+        }
+    }
+
+    /// Fraction of rockets across all islands that reached the target in the current generation
+    pub fn success_rate(&self) -> f32 {
+        let total: f32 = self.islands.iter().map(|p| p.success_rate()).sum();
+        total / self.islands.len() as f32
+    }
+
+    /// Fitness of the fittest rocket across all islands in the current generation
+    pub fn best_fitness(&self) -> f32 {
+        self.islands
+            .iter()
+            .map(|p| p.best_fitness())
+            .fold(0.0, f32::max)
+    }
+
+    /// Average fitness of the rockets across all islands in the current generation
+    pub fn mean_fitness(&self) -> f32 {
+        let fitness: Vec<f32> = self.rockets().map(|r| r.fitness()).collect();
+        fitness.iter().sum::<f32>() / fitness.len().max(1) as f32
+    }
+
+    /// Snapshot of the progress of the simulation
+    pub fn stats(&self) -> SimulationStats {
+        let count = |state| self.rockets().filter(|r| r.state() == state).count();
+        SimulationStats {
+            generation: self.generation_count,
+            frame: self.frame_idx,
+            alive: count(RocketState::Alive),
+            crashed: count(RocketState::Crashed),
+            completed: count(RocketState::Completed),
+            success_rate: self.success_rate(),
+            best_fitness: self.best_fitness(),
+            mean_fitness: self.mean_fitness(),
+            species_count: self.species_count(),
+            mean_genome_length: self.mean_genome_length(),
+            frames_run: self.frames_run,
+            frames_saved: self.frames_saved,
+        }
+    }
+
+    /// Index of the frame of the current generation, 0 between generations
+    pub fn frame_idx(&self) -> usize {
+        self.frame_idx
+    }
+
+    /// Parameters the simulation evolves with, after clamping, refer `with_params`
+    pub fn params(&self) -> &EvolutionParams {
+        &self.params
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Sub-populations (islands) of rockets
+    pub fn islands(&self) -> &[Population] {
+        &self.islands
+    }
+
+    /// Rockets of the current generation across all islands
+    pub fn rockets(&self) -> impl Iterator<Item = &Rocket> {
+        self.islands.iter().flat_map(|p| p.rockets())
+    }
+
+    /// Replace rockets of the next generation with rockets carrying `genomes`,
+    /// e.g to seed the simulation with known solutions
+    ///
+    /// Genomes are spread across the islands, replacing the last rockets
+    /// created by reproduction, so they still face selection at the end of the generation
+    pub fn inject_genomes(&mut self, genomes: Vec<Dna>) {
+        self.injected.extend(genomes);
+    }
+
+    /// Notify `observer` of the events of the simulation from now on, refer `SimulationObserver`
+    ///
+    /// To read the state of an observer once registered, share it with `Arc<Mutex<_>>`
+    pub fn add_observer(&mut self, observer: Box<dyn SimulationObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Record the DNA of the rockets of the last `capacity` generations, so they can be replayed
+    /// Recording is off by default, a `capacity` of 0 turns it off and drops the recording
+    pub fn set_recording(&mut self, capacity: usize) {
+        self.recording = Recording::new(capacity);
+    }
+
+    /// Past generations that can be replayed, refer `set_recording`
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// Frames simulated over all generations
+    pub fn frames_run(&self) -> u64 {
+        self.frames_run
+    }
+
+    /// Frames skipped over all generations by ending generations early
+    pub fn frames_saved(&self) -> u64 {
+        self.frames_saved
+    }
+
+    /// Fraction of the frames skipped by ending generations early, between 0 and 1
+    pub fn saved_ratio(&self) -> f32 {
+        let total = self.frames_run + self.frames_saved;
+        self.frames_saved as f32 / total.max(1) as f32
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Changes to the spawn and target locations take effect from the next generation
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
     }
 
     /// Draw the simulation to the window
     pub fn draw(&self, draw: &Draw) {
-        self.world.draw(&draw);
-        self.population.draw(&draw);
+        self.world.draw(draw);
+        self.world.draw_obstacles(draw, self.frame_idx);
+        self.islands.iter().for_each(|p| p.draw(draw));
+    }
+
+    /// Draw the trajectories of the fittest rockets of every island
+    pub fn draw_trails(&self, draw: &Draw) {
+        self.islands.iter().for_each(|p| p.draw_trails(draw));
+    }
+
+    /// Number of species across all islands in the previous generation,
+    /// 0 unless fitness sharing is enabled
+    pub fn species_count(&self) -> usize {
+        self.islands.iter().map(|p| p.species_count()).sum()
+    }
+
+    /// Average number of genes of the rockets across all islands
+    pub fn mean_genome_length(&self) -> f32 {
+        let total: f32 = self.islands.iter().map(|p| p.mean_genome_length()).sum();
+        total / self.islands.len() as f32
+    }
+
+    /// Objectives (target distance, fuel, frames) of the rockets on the Pareto front
+    /// of every island in the previous generation,
+    /// empty unless the selection method is NSGA-II
+    pub fn pareto_front(&self) -> Vec<[f32; 3]> {
+        // Rockets on the front of an island can be dominated by those of another
+        let fronts: Vec<_> = self
+            .islands
+            .iter()
+            .flat_map(|p| p.pareto_front().iter().cloned())
+            .collect();
+        let ranks = nsga2::non_dominated_sort(&fronts);
+        fronts
+            .into_iter()
+            .zip(ranks)
+            .filter(|&(_, rank)| rank == 0)
+            .map(|(objectives, _)| objectives)
+            .collect()
+    }
+
+    /// Write the Pareto front to `path` as CSV
+    pub fn write_pareto_csv(&self, path: &str) -> io::Result<()> {
+        nsga2::write_front_csv(
+            path,
+            ["target_distance", "fuel", "frames"],
+            &self.pareto_front(),
+        )
+    }
+
+    pub fn heatmap(&self) -> &Heatmap {
+        &self.heatmap
+    }
+
+    /// Draw the `layer` heatmap of the current generation, or of all generations if `cumulative`
+    pub fn draw_heatmap(&self, draw: &Draw, layer: HeatmapLayer, cumulative: bool) {
+        self.heatmap.draw(draw, &self.world, layer, cumulative);
+    }
+
+    /// Check if the generation that is running (or is about to begin)
+    /// must be played in real time while fast-forwarding
+    fn is_snapshot_generation(&self, render_interval: u32) -> bool {
+        if render_interval == 0 {
+            return false;
+        }
+
+        let generation = match self.frame_idx {
+            0 => self.generation_count + 1,
+            _ => self.generation_count,
+        };
+        generation % render_interval == 0
     }
 
     fn start_new_generation(&mut self) {
         self.generation_count += 1;
-        self.population.reproduction();
+
+        let mut injected = vec![Vec::new(); self.islands.len()];
+        for (i, dna) in self.injected.drain(..).enumerate() {
+            injected[i % self.islands.len()].push(Rocket::new(Some(&dna)));
+        }
+
+        for (island, rockets) in self.islands.iter_mut().zip(injected) {
+            island.reproduction();
+            island.inject(rockets);
+            island.reset(&self.world);
+        }
+        self.heatmap.clear_generation();
+
+        if self.recording.capacity() > 0 {
+            let genomes = self.rockets().map(|r| r.dna().clone()).collect();
+            self.recording.record(self.generation_count, genomes);
+        }
+
+        for observer in &mut self.observers {
+            observer.on_generation_start(self.generation_count);
+        }
+    }
+
+    /// Notify the observers of the end of the current generation,
+    /// and of its fittest rocket if no rocket was ever fitter
+    fn notify_generation_end(&mut self) {
+        let stats = self.stats();
+        let is_new_best = stats.best_fitness > self.all_time_best;
+        if is_new_best {
+            self.all_time_best = stats.best_fitness;
+        }
+
+        for observer in &mut self.observers {
+            observer.on_generation_end(stats.generation, &stats);
+            if is_new_best {
+                observer.on_new_best_fitness(stats.generation, stats.best_fitness);
+            }
+        }
     }
 
     fn end_current_generation(&mut self) {
@@ -67,6 +535,173 @@ impl Simulation {
         }
 
         self.frame_idx = 0;
-        self.population.selection();
+
+        // Migrants take part in the selection of their new island
+        let migration = self.params.migration;
+        if self.generation_count.is_multiple_of(migration.interval) {
+            island::migrate(&mut self.islands, migration.count, migration.topology);
+        }
+        self.islands.iter_mut().for_each(|p| p.selection());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{MapGenerator, MapKind};
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+
+    /// Position and fitness bits of every rocket after `run_generations(3)` with seed 42,
+    /// regenerate it with `cargo test update_expected -- --ignored`
+    /// after an intended change to the evolution
+    const EXPECTED_PATH: &str = "tests/data/simulation_seed_42.txt";
+
+    /// Lines of `EXPECTED_PATH` for the current evolution
+    fn seeded_run() -> Vec<String> {
+        let mut simulation = Simulation::with_seed(World::new().unwrap(), 42);
+        simulation.run_generations(3);
+
+        // Bits, so that serial and parallel runs have to match exactly
+        simulation
+            .rockets()
+            .map(|r| {
+                let bits = [r.pos.x, r.pos.y, r.fitness()].map(f32::to_bits);
+                format!("{:08x} {:08x} {:08x}", bits[0], bits[1], bits[2])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let actual = seeded_run();
+        let expected = fs::read_to_string(EXPECTED_PATH).unwrap();
+        let expected: Vec<&str> = expected.lines().collect();
+        assert_eq!(actual.len(), expected.len());
+        for (i, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+            assert_eq!(actual, expected, "rocket {}", i);
+        }
+    }
+
+    #[test]
+    #[ignore = "overwrites the expected rockets of `seeded_runs_are_reproducible`"]
+    fn update_expected() {
+        fs::write(EXPECTED_PATH, seeded_run().join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn rockets_are_spread_across_islands() {
+        for (num_rockets, num_islands, sizes) in [
+            (10, 3, vec![4, 3, 3]),
+            (2, 5, vec![1, 1]),
+            (0, 2, vec![1]),
+            (7, 0, vec![7]),
+        ] {
+            let params = EvolutionParams {
+                num_rockets,
+                num_islands,
+                ..EvolutionParams::default()
+            };
+            let world = MapGenerator::new(42).generate(MapKind::Obstacles);
+            let simulation = Simulation::with_params(world, 42, params);
+            let islands: Vec<_> = simulation
+                .islands()
+                .iter()
+                .map(|p| p.rockets().len())
+                .collect();
+            assert_eq!(islands, sizes);
+        }
+    }
+
+    #[test]
+    fn pareto_front_spans_every_island() {
+        let world = MapGenerator::new(42).generate(MapKind::Obstacles);
+        let params = EvolutionParams {
+            num_rockets: 60,
+            num_islands: 3,
+            selection_method: SelectionMethod::Nsga2,
+            ..EvolutionParams::default()
+        };
+        let mut simulation = Simulation::with_params(world, 42, params);
+        simulation.run_generations(3);
+        simulation.update();
+
+        let front = simulation.pareto_front();
+        let island_fronts: usize = simulation
+            .islands()
+            .iter()
+            .map(|p| p.pareto_front().len())
+            .sum();
+        assert!(!front.is_empty() && front.len() <= island_fronts);
+        assert!(nsga2::non_dominated_sort(&front)
+            .iter()
+            .all(|&rank| rank == 0));
+    }
+
+    /// Generations and fitness of every new best fitness notification
+    #[derive(Default)]
+    struct BestFitness(Vec<(u32, f32)>);
+
+    impl SimulationObserver for BestFitness {
+        fn on_new_best_fitness(&mut self, generation: u32, fitness: f32) {
+            self.0.push((generation, fitness));
+        }
+    }
+
+    #[test]
+    fn new_best_fitness_needs_a_strict_improvement() {
+        let world = MapGenerator::new(42).generate(MapKind::Obstacles);
+        let params = EvolutionParams {
+            num_rockets: 20,
+            ..EvolutionParams::default()
+        };
+        let best = Arc::new(Mutex::new(BestFitness::default()));
+        let mut simulation = Simulation::with_params(world, 42, params);
+        simulation.add_observer(Box::new(best.clone()));
+        simulation.run_generation();
+        let fitness = simulation.best_fitness();
+        assert!(fitness > 0.0);
+        assert_eq!(best.lock().unwrap().0, vec![(1, fitness)]);
+
+        // Equal to the best so far
+        simulation.notify_generation_end();
+        assert_eq!(best.lock().unwrap().0.len(), 1);
+
+        simulation.all_time_best = fitness * 0.5;
+        simulation.notify_generation_end();
+        assert_eq!(best.lock().unwrap().0, vec![(1, fitness), (1, fitness)]);
+    }
+
+    #[test]
+    fn new_worlds_keep_the_evolution_settings() {
+        let params = EvolutionParams {
+            num_rockets: 30,
+            num_islands: 2,
+            lifespan: 150,
+            ..EvolutionParams::default()
+        };
+        let corridors = || MapGenerator::new(42).generate(MapKind::Corridors);
+        let mut simulation = Simulation::with_params(
+            MapGenerator::new(42).generate(MapKind::Obstacles),
+            7,
+            params,
+        );
+        simulation.set_recording(2);
+        simulation.run_generations(2);
+
+        simulation.set_world(corridors());
+        assert_eq!(
+            (simulation.generation_count, simulation.frame_idx()),
+            (0, 0)
+        );
+        assert_eq!((simulation.params(), simulation.seed()), (&params, 7));
+        assert_eq!(simulation.recording().capacity(), 2);
+
+        // Same as a simulation created in the new world
+        let mut fresh = Simulation::with_params(corridors(), 7, params);
+        simulation.run_generation();
+        fresh.run_generation();
+        let positions = |s: &Simulation| s.rockets().map(|r| r.pos).collect::<Vec<_>>();
+        assert_eq!(positions(&simulation), positions(&fresh));
     }
 }
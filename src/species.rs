@@ -0,0 +1,119 @@
+//! Speciation and fitness sharing
+//!
+//! Rockets are clustered into species of similar genomes, the fitness of every rocket
+//! is then shared among the members of its species
+//!
+//! A route found by many rockets is penalized by its crowd, which keeps alternative routes
+//! (found by a few rockets) alive long enough to be explored
+//! https://en.wikipedia.org/wiki/Fitness_sharing
+
+use crate::rocket::Rocket;
+use crate::{FITNESS_SHARING, SPECIES_DISTANCE_METRIC, SPECIES_DISTANCE_THRESHOLD};
+use serde::{Deserialize, Serialize};
+
+/// How the distance between two rockets is measured
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    /// Average distance between corresponding genes of the `Dna`
+    Genes,
+    /// Average distance between the positions of the rockets at every frame,
+    /// requires `ROCKET_RECORD_PATH`
+    Trajectory,
+}
+
+/// Settings of speciation and fitness sharing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SharingParams {
+    /// Share the fitness of every rocket among the members of its species
+    pub enabled: bool,
+    pub metric: DistanceMetric,
+    /// Rockets closer than this to a species representative belong to that species
+    pub threshold: f32,
+}
+
+impl Default for SharingParams {
+    /// `FITNESS_SHARING`, `SPECIES_DISTANCE_METRIC` and `SPECIES_DISTANCE_THRESHOLD`
+    fn default() -> Self {
+        SharingParams {
+            enabled: FITNESS_SHARING,
+            metric: SPECIES_DISTANCE_METRIC,
+            threshold: SPECIES_DISTANCE_THRESHOLD,
+        }
+    }
+}
+
+/// Assign every rocket in `agents` to a species
+///
+/// A rocket joins the first species whose representative (first member)
+/// is within `threshold` of it, as measured by `metric`,
+/// if there is none, it becomes the representative of a new species
+///
+/// Returns the species index of every rocket, and the number of species
+pub fn speciate(agents: &[Rocket], metric: DistanceMetric, threshold: f32) -> (Vec<usize>, usize) {
+    let mut representatives: Vec<&Rocket> = Vec::new();
+    let mut species = Vec::with_capacity(agents.len());
+
+    for rocket in agents {
+        let found = representatives
+            .iter()
+            .position(|r| rocket.distance(r, metric) <= threshold);
+
+        match found {
+            Some(idx) => species.push(idx),
+            None => {
+                species.push(representatives.len());
+                representatives.push(rocket);
+            }
+        }
+    }
+
+    (species, representatives.len())
+}
+
+/// Divide every fitness in `weights` by the size of its rocket's species
+/// `species` are the species indices as returned by `speciate`
+pub fn share_fitness(weights: &mut [f32], species: &[usize], species_count: usize) {
+    let mut sizes = vec![0; species_count];
+    species.iter().for_each(|&s| sizes[s] += 1);
+
+    for (w, &s) in weights.iter_mut().zip(species) {
+        *w /= sizes[s] as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::glam::vec2;
+
+    #[test]
+    fn rockets_join_the_first_species_within_the_threshold() {
+        // Without a recorded path, the trajectory distance is between the positions
+        let agents: Vec<Rocket> = [0.0, 5.0, 12.0, 100.0, 104.0, 8.0]
+            .iter()
+            .map(|&x| {
+                let mut rocket = Rocket::new(None);
+                rocket.pos = vec2(x, 0.0);
+                rocket
+            })
+            .collect();
+
+        // 12 is within the threshold of 5 but not of the representative 0,
+        // 8 is closer to the representative 12 but joins the first species
+        let (species, count) = speciate(&agents, DistanceMetric::Trajectory, 10.0);
+        assert_eq!(species, vec![0, 0, 1, 2, 2, 0]);
+        assert_eq!(count, 3);
+
+        let (species, count) = speciate(&agents, DistanceMetric::Trajectory, 0.0);
+        assert_eq!(species, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(count, 6);
+    }
+
+    #[test]
+    fn fitness_is_divided_by_the_species_size() {
+        let mut weights = [6.0, 4.0, 3.0, 8.0, 2.0, 1.0];
+        share_fitness(&mut weights, &[0, 0, 1, 2, 2, 0], 3);
+        assert_eq!(weights, [2.0, 4.0 / 3.0, 3.0, 4.0, 1.0, 1.0 / 3.0]);
+    }
+}
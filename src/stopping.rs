@@ -0,0 +1,209 @@
+//! Stopping criteria for evolution runs
+//!
+//! A headless run evolves generation after generation until one of the criteria is met,
+//! then reports which criterion fired in a `RunSummary`
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::{
+    MAX_GENERATIONS, PLATEAU_GENERATIONS, PLATEAU_TOLERANCE, TARGET_SUCCESS_RATE, TIME_BUDGET,
+};
+
+/// When to stop an evolution run, every criterion set to `None` is ignored
+///
+/// A run without any criteria never stops
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StoppingCriteria {
+    /// Number of generations to run
+    pub max_generations: Option<u32>,
+    /// Fraction of rockets reaching the target in a generation, between 0 and 1
+    pub success_rate: Option<f32>,
+    /// Number of generations in a row without the best fitness improving
+    /// by more than `plateau_tolerance` (relative)
+    pub plateau_generations: Option<u32>,
+    pub plateau_tolerance: f32,
+    /// Wall-clock time, checked at the end of every generation
+    pub time_budget: Option<Duration>,
+}
+
+impl Default for StoppingCriteria {
+    fn default() -> Self {
+        StoppingCriteria::from_configs()
+    }
+}
+
+impl StoppingCriteria {
+    /// Stopping criteria defined by `MAX_GENERATIONS`, `TARGET_SUCCESS_RATE`,
+    /// `PLATEAU_GENERATIONS`, `PLATEAU_TOLERANCE` and `TIME_BUDGET`
+    pub fn from_configs() -> Self {
+        StoppingCriteria {
+            max_generations: MAX_GENERATIONS,
+            success_rate: TARGET_SUCCESS_RATE,
+            plateau_generations: PLATEAU_GENERATIONS,
+            plateau_tolerance: PLATEAU_TOLERANCE,
+            time_budget: TIME_BUDGET,
+        }
+    }
+
+    /// The first criterion met by a run in the state described by `progress`, if any
+    /// Criteria are checked in order, success rate, maximum generations, plateau and time budget
+    pub fn check(&self, progress: &RunProgress) -> Option<StopReason> {
+        if self
+            .success_rate
+            .is_some_and(|rate| progress.success_rate >= rate)
+        {
+            return Some(StopReason::SuccessRate);
+        }
+        if self
+            .max_generations
+            .is_some_and(|max| progress.generations >= max)
+        {
+            return Some(StopReason::MaxGenerations);
+        }
+        if self
+            .plateau_generations
+            .is_some_and(|max| progress.stale_generations >= max)
+        {
+            return Some(StopReason::Plateau);
+        }
+        if self
+            .time_budget
+            .is_some_and(|budget| progress.elapsed >= budget)
+        {
+            return Some(StopReason::TimeBudget);
+        }
+
+        None
+    }
+}
+
+/// State of a run, at the end of a generation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RunProgress {
+    /// Generations run so far
+    pub generations: u32,
+    /// Fraction of rockets that reached the target in the last generation
+    pub success_rate: f32,
+    /// Best fitness over all generations
+    pub best_fitness: f32,
+    /// Generations in a row without the best fitness improving
+    pub stale_generations: u32,
+    pub elapsed: Duration,
+}
+
+impl RunProgress {
+    /// Record the results of a generation that just ended
+    pub fn record(&mut self, success_rate: f32, fitness: f32, tolerance: f32, elapsed: Duration) {
+        self.generations += 1;
+        self.success_rate = success_rate;
+        self.elapsed = elapsed;
+
+        if fitness > self.best_fitness * (1.0 + tolerance) {
+            self.stale_generations = 0;
+        } else {
+            self.stale_generations += 1;
+        }
+        self.best_fitness = self.best_fitness.max(fitness);
+    }
+}
+
+/// The stopping criterion that ended a run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    MaxGenerations,
+    SuccessRate,
+    Plateau,
+    TimeBudget,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            StopReason::MaxGenerations => "maximum generations reached",
+            StopReason::SuccessRate => "target success rate reached",
+            StopReason::Plateau => "fitness plateaued",
+            StopReason::TimeBudget => "time budget exhausted",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Results of a finished run
+#[derive(Clone, Copy, Debug)]
+pub struct RunSummary {
+    pub reason: StopReason,
+    pub progress: RunProgress,
+    /// Frames simulated, and frames skipped by ending generations early
+    pub frames_run: u64,
+    pub frames_saved: u64,
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let p = &self.progress;
+        writeln!(f, "Stopped: {}", self.reason)?;
+        writeln!(f, "Generations: {}", p.generations)?;
+        writeln!(f, "Success rate: {:.1}%", p.success_rate * 100.0)?;
+        writeln!(f, "Best fitness: {}", p.best_fitness)?;
+        writeln!(f, "Stale generations: {}", p.stale_generations)?;
+        writeln!(f, "Elapsed: {:.2?}", p.elapsed)?;
+        write!(
+            f,
+            "Frames: {} run, {} saved",
+            self.frames_run, self.frames_saved
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn criteria_are_checked_in_order() {
+        let progress = RunProgress {
+            generations: 50,
+            success_rate: 0.95,
+            best_fitness: 1.0,
+            stale_generations: 20,
+            elapsed: Duration::from_secs(10),
+        };
+        let mut criteria = StoppingCriteria {
+            max_generations: Some(50),
+            success_rate: Some(0.9),
+            plateau_generations: Some(20),
+            plateau_tolerance: 0.01,
+            time_budget: Some(Duration::from_secs(10)),
+        };
+
+        assert_eq!(criteria.check(&progress), Some(StopReason::SuccessRate));
+        criteria.success_rate = None;
+        assert_eq!(criteria.check(&progress), Some(StopReason::MaxGenerations));
+        criteria.max_generations = None;
+        assert_eq!(criteria.check(&progress), Some(StopReason::Plateau));
+        criteria.plateau_generations = None;
+        assert_eq!(criteria.check(&progress), Some(StopReason::TimeBudget));
+        criteria.time_budget = None;
+        assert_eq!(criteria.check(&progress), None);
+    }
+
+    #[test]
+    fn plateau_needs_an_improvement_above_the_tolerance() {
+        let tolerance = 0.01;
+        let mut progress = RunProgress::default();
+        progress.record(0.0, 100.0, tolerance, Duration::ZERO);
+        assert_eq!(progress.stale_generations, 0);
+
+        // Within 1% of the best fitness
+        progress.record(0.0, 100.9, tolerance, Duration::ZERO);
+        progress.record(0.0, 99.0, tolerance, Duration::ZERO);
+        assert_eq!(progress.stale_generations, 2);
+        assert_eq!(progress.best_fitness, 100.9);
+
+        // Just over 1% of the best fitness
+        progress.record(0.0, 102.0, tolerance, Duration::ZERO);
+        assert_eq!(progress.stale_generations, 0);
+        assert_eq!(progress.generations, 4);
+    }
+}
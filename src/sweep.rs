@@ -0,0 +1,380 @@
+//! Parameter sweeps
+//!
+//! Runs an experiment headlessly for every combination of the swept parameter values,
+//! with several seeds each, and aggregates the results into a table
+//! of means with 95% confidence intervals
+//!
+//! The values are listed in the `[sweep]` section of an experiment file,
+//! parameters left out keep the experiment's value
+//!
+//! ```toml
+//! seed = 1
+//!
+//! [stopping]
+//! max_generations = 200
+//!
+//! [sweep]
+//! mutation_probability = [5, 10, 20]
+//! mutation_variation = [0.25, 0.5]
+//! population = [250, 750]
+//! selection = ["fitness_proportionate", "nsga2"]
+//! seeds = 5
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+
+use crate::experiment::Experiment;
+use crate::population::SelectionMethod;
+use crate::simulation::{EvolutionParams, Simulation};
+use crate::stopping::RunSummary;
+use crate::SWEEP_SEEDS;
+use serde::{Deserialize, Serialize};
+
+/// Parameter values to sweep over
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SweepSpec {
+    #[serde(default)]
+    pub mutation_probability: Vec<u8>,
+    #[serde(default)]
+    pub mutation_variation: Vec<f32>,
+    #[serde(default)]
+    pub population: Vec<usize>,
+    #[serde(default)]
+    pub selection: Vec<SelectionMethod>,
+    /// Number of runs of every combination, `SWEEP_SEEDS` if not set
+    pub seeds: Option<usize>,
+}
+
+impl SweepSpec {
+    /// Every combination of the swept values, on top of `base`
+    pub fn combinations(&self, base: EvolutionParams) -> Vec<EvolutionParams> {
+        let mut combinations = Vec::new();
+        for &probability in &or_base(&self.mutation_probability, base.mutation.probability) {
+            for &variation in &or_base(&self.mutation_variation, base.mutation.variation) {
+                for &num_rockets in &or_base(&self.population, base.num_rockets) {
+                    for &selection_method in &or_base(&self.selection, base.selection_method) {
+                        let mut params = base;
+                        params.mutation.probability = probability;
+                        params.mutation.variation = variation;
+                        params.num_rockets = num_rockets;
+                        params.selection_method = selection_method;
+                        combinations.push(params);
+                    }
+                }
+            }
+        }
+
+        combinations
+    }
+}
+
+/// The swept `values`, or `base` alone if there are none
+fn or_base<T: Copy>(values: &[T], base: T) -> Vec<T> {
+    match values.is_empty() {
+        true => vec![base],
+        false => values.to_vec(),
+    }
+}
+
+/// Mean of a sample, with the half width of its 95% confidence interval
+/// The half width is `NaN` for less than two values
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub mean: f32,
+    pub half_width: f32,
+}
+
+impl Estimate {
+    /// Estimate the mean of `values` using the Student's t-distribution
+    pub fn new(values: &[f32]) -> Self {
+        let n = values.len();
+        let mean = values.iter().sum::<f32>() / n.max(1) as f32;
+        if n < 2 {
+            return Estimate {
+                mean,
+                half_width: f32::NAN,
+            };
+        }
+
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / (n - 1) as f32;
+        let half_width = t_critical(n - 1) * (variance / n as f32).sqrt();
+        Estimate { mean, half_width }
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.4} ± {:.4}", self.mean, self.half_width)
+    }
+}
+
+/// Runs of a single combination of parameters
+pub struct SweepResult {
+    pub params: EvolutionParams,
+    pub runs: Vec<RunSummary>,
+}
+
+impl SweepResult {
+    pub fn success_rate(&self) -> Estimate {
+        self.estimate(|r| r.progress.success_rate)
+    }
+
+    pub fn best_fitness(&self) -> Estimate {
+        self.estimate(|r| r.progress.best_fitness)
+    }
+
+    pub fn generations(&self) -> Estimate {
+        self.estimate(|r| r.progress.generations as f32)
+    }
+
+    pub fn elapsed_secs(&self) -> Estimate {
+        self.estimate(|r| r.progress.elapsed.as_secs_f32())
+    }
+
+    fn estimate(&self, metric: impl Fn(&RunSummary) -> f32) -> Estimate {
+        let values: Vec<f32> = self.runs.iter().map(metric).collect();
+        Estimate::new(&values)
+    }
+}
+
+/// Run `experiment` for every combination of its `[sweep]` values,
+/// `on_run` is called after every run with its parameters, seed and summary
+///
+/// Run `i` of every combination is seeded with the experiment's seed plus `i`,
+/// so combinations are compared on the same seeds
+///
+/// Runs are done one after the other so that `on_run` reports them in order,
+/// the rockets of every run are still updated in parallel with the `parallel` feature
+pub fn run_sweep<F>(experiment: &Experiment, mut on_run: F) -> io::Result<Vec<SweepResult>>
+where
+    F: FnMut(&EvolutionParams, u64, &RunSummary),
+{
+    let spec = experiment.sweep.clone().unwrap_or_default();
+    let seeds = spec.seeds.unwrap_or(SWEEP_SEEDS);
+    let base_seed = experiment.seed();
+    let criteria = experiment.stopping_criteria();
+
+    let mut results = Vec::new();
+    for params in spec.combinations(experiment.params()) {
+        let mut runs = Vec::with_capacity(seeds);
+        for i in 0..seeds as u64 {
+            let seed = base_seed.wrapping_add(i);
+            let mut simulation = Simulation::with_params(experiment.world()?, seed, params);
+            let summary = simulation.run(&criteria);
+            on_run(&params, seed, &summary);
+            runs.push(summary);
+        }
+        results.push(SweepResult { params, runs });
+    }
+
+    Ok(results)
+}
+
+const CSV_HEADER: [&str; 13] = [
+    "mutation_probability",
+    "mutation_variation",
+    "population",
+    "selection",
+    "runs",
+    "success_rate_mean",
+    "success_rate_ci95",
+    "best_fitness_mean",
+    "best_fitness_ci95",
+    "generations_mean",
+    "generations_ci95",
+    "elapsed_secs_mean",
+    "elapsed_secs_ci95",
+];
+
+/// Write the aggregated `results` to `path` as CSV, a row per combination
+pub fn write_sweep_csv(path: &str, results: &[SweepResult]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", CSV_HEADER.join(","))?;
+    for result in results {
+        let p = &result.params;
+        write!(
+            writer,
+            "{},{},{},{},{}",
+            p.mutation.probability,
+            p.mutation.variation,
+            p.num_rockets,
+            p.selection_method,
+            result.runs.len()
+        )?;
+        for e in [
+            result.success_rate(),
+            result.best_fitness(),
+            result.generations(),
+            result.elapsed_secs(),
+        ] {
+            write!(writer, ",{},{}", e.mean, e.half_width)?;
+        }
+        writeln!(writer)?;
+    }
+
+    writer.flush()
+}
+
+/// Format the aggregated `results` as a text table
+pub fn sweep_table(results: &[SweepResult]) -> String {
+    let mut table = format!(
+        "{:>5} {:>9} {:>6} {:>22} {:>18} {:>24} {:>20}\n",
+        "mut", "variation", "pop", "selection", "success rate", "best fitness", "generations"
+    );
+    for result in results {
+        let p = &result.params;
+        table += &format!(
+            "{:>5} {:>9} {:>6} {:>22} {:>18} {:>24} {:>20}\n",
+            p.mutation.probability,
+            p.mutation.variation,
+            p.num_rockets,
+            p.selection_method.to_string(),
+            result.success_rate().to_string(),
+            result.best_fitness().to_string(),
+            result.generations().to_string(),
+        );
+    }
+
+    table
+}
+
+/// Two-sided 95% critical value of the Student's t-distribution
+/// with `df` degrees of freedom
+///
+/// Tabulated up to 30 degrees of freedom, then approximated from the normal critical value
+/// with the Cornish-Fisher expansion, accurate to 4 decimals from there on
+fn t_critical(df: usize) -> f32 {
+    const TABLE: [f32; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    const Z: f64 = 1.959964;
+
+    match df {
+        0 => f32::NAN,
+        1..=30 => TABLE[df - 1],
+        _ => {
+            let (z, df) = (Z, df as f64);
+            let terms = [
+                (z.powi(3) + z) / 4.0,
+                (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / 96.0,
+                (3.0 * z.powi(7) + 19.0 * z.powi(5) + 17.0 * z.powi(3) - 15.0 * z) / 384.0,
+            ];
+            let t = terms
+                .iter()
+                .enumerate()
+                .map(|(i, term)| term / df.powi(i as i32 + 1))
+                .sum::<f64>();
+            (z + t) as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stopping::{RunProgress, StopReason};
+    use std::fs;
+    use std::time::Duration;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    fn run(success_rate: f32, generations: u32) -> RunSummary {
+        RunSummary {
+            reason: StopReason::MaxGenerations,
+            progress: RunProgress {
+                generations,
+                success_rate,
+                best_fitness: 1.0,
+                stale_generations: 0,
+                elapsed: Duration::from_secs(2),
+            },
+            frames_run: 0,
+            frames_saved: 0,
+        }
+    }
+
+    #[test]
+    fn estimate_uses_the_t_distribution() {
+        // Sample standard deviation of 1.5811, standard error of 0.7071
+        let estimate = Estimate::new(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_close(estimate.mean, 3.0);
+        assert_close(estimate.half_width, 2.776 * 0.5f32.sqrt());
+
+        let single = Estimate::new(&[4.0]);
+        assert_close(single.mean, 4.0);
+        assert!(single.half_width.is_nan());
+    }
+
+    #[test]
+    fn t_critical_is_continuous_past_the_table() {
+        // Exact values from the t-distribution
+        for (df, t) in [(31, 2.0395), (40, 2.0211), (60, 2.0003), (120, 1.9799)] {
+            assert_close(t_critical(df), t);
+        }
+        assert!(t_critical(30) > t_critical(31));
+        assert_close(t_critical(100_000), 1.96);
+    }
+
+    #[test]
+    fn combinations_cover_every_swept_value() {
+        let spec = SweepSpec {
+            mutation_probability: vec![5, 10, 20],
+            population: vec![100, 200],
+            selection: vec![
+                SelectionMethod::FitnessProportionate,
+                SelectionMethod::Nsga2,
+            ],
+            ..SweepSpec::default()
+        };
+        let base = EvolutionParams::default();
+        let combinations = spec.combinations(base);
+        assert_eq!(combinations.len(), 12);
+        assert!(combinations
+            .iter()
+            .all(|p| p.mutation.variation == base.mutation.variation));
+        assert_eq!(combinations[1].selection_method, SelectionMethod::Nsga2);
+        assert_eq!(combinations[2].num_rockets, 200);
+        assert_eq!(combinations[4].mutation.probability, 10);
+
+        assert_eq!(SweepSpec::default().combinations(base), vec![base]);
+    }
+
+    #[test]
+    fn csv_has_a_row_per_combination() {
+        let results = vec![SweepResult {
+            params: EvolutionParams::default(),
+            runs: vec![run(0.25, 10), run(0.75, 30)],
+        }];
+        let path = std::env::temp_dir().join("genetic_rockets_sweep_test.csv");
+        write_sweep_csv(path.to_str().unwrap(), &results).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADER.join(","));
+
+        let p = &results[0].params;
+        let row: Vec<&str> = lines[1].split(',').collect();
+        let params = format!(
+            "{},{},{},fitness_proportionate,2",
+            p.mutation.probability, p.mutation.variation, p.num_rockets
+        );
+        assert_eq!(row[..5].join(","), params);
+
+        // Two runs, the half width is 12.706 times the standard error
+        let estimates = [0.5, 3.1765, 1.0, 0.0, 20.0, 127.06, 2.0, 0.0];
+        for (value, expected) in row[5..].iter().zip(estimates) {
+            assert_close(value.parse().unwrap(), expected);
+        }
+        assert_eq!(row.len(), CSV_HEADER.len());
+    }
+}
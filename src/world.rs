@@ -2,10 +2,15 @@
 
 use std::fs::File;
 use std::io;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 
+use crate::field::{self, ForceField};
+use crate::obstacle::Obstacle;
+use crate::shape::Shape;
 use crate::{
-    GRID_COLOR, MAP_FILE_PATH, SCREEN_DIMENSIONS, TARGET_COLOR, TARGET_LOCATION, TARGET_RADIUS,
+    CHECKPOINT_COLOR, EDITOR_GRID_COLOR, EDITOR_SPAWN_COLOR, GRAVITY, GRID_COLOR, MAP_FILE_PATH,
+    ROCKET_SPAWN_LOCATION, SCENE_FILE_PATH, SCREEN_DIMENSIONS, TARGET_COLOR, TARGET_LOCATION,
+    TARGET_RADIUS,
 };
 use nannou::prelude::*;
 use nannou::Draw;
@@ -21,91 +26,556 @@ pub struct World {
     grid_size: usize,
     /// Size of a unit block which represents either wall/no-wall
     block_size: f32,
+    /// The world position rockets are launched from
+    spawn: Vec2,
+    /// The world position rockets are trying to reach
+    target: Vec2,
+    /// World positions rockets have to pass through, in order, before reaching the `target`
+    checkpoints: Vec<Vec2>,
+    /// Obstacles whose position depends on the frame, refer `Obstacle`
+    obstacles: Vec<Obstacle>,
+    /// Regions pushing the rockets flying through them, refer `ForceField`
+    fields: Vec<ForceField>,
+    /// Walls of arbitrary geometry, refer `Shape`
+    shapes: Vec<Shape>,
+    /// Force applied to every rocket everywhere, `GRAVITY` by default
+    gravity: Vec2,
+    /// Map file the world was loaded from, `None` if it wasn't loaded from a file
+    path: Option<String>,
+    /// Number of obstacles, force fields and shapes defined in the map,
+    /// the ones after them come from a scene file or were added later
+    map_entities: EntityCounts,
+}
+
+/// Number of obstacles, force fields and shapes
+#[derive(Clone, Copy, Debug, Default)]
+struct EntityCounts {
+    obstacles: usize,
+    fields: usize,
+    shapes: usize,
 }
 
 /// A wall is a solid block that a rocket can't pass through
 pub trait Wall<T> {
     /// Check if a wall exists exists at `pos`
     fn is_wall(&self, pos: T) -> bool;
+    /// Check if a wall exists at `pos` at frame `frame_idx` of a generation,
+    /// i.e including the moving obstacles
+    fn is_wall_at(&self, pos: T, _frame_idx: usize) -> bool {
+        self.is_wall(pos)
+    }
+}
+
+/// Contents of a parsed map file
+#[derive(Default)]
+struct MapData {
+    grid_size: usize,
+    walls: Vec<(usize, usize)>,
+    /// Grid position of the spawn block `S`, if any
+    spawn: Option<(usize, usize)>,
+    /// Grid position of the target block `T`, if any
+    target: Option<(usize, usize)>,
+    /// Grid positions of the checkpoint blocks `a` to `z`, with their letter
+    checkpoints: Vec<(char, (usize, usize))>,
+    obstacles: Vec<Obstacle>,
+    fields: Vec<ForceField>,
+    shapes: Vec<Shape>,
+}
+
+impl MapData {
+    /// Parse an obstacle, force field or shape definition
+    /// Returns `false` if `line` isn't a valid definition
+    fn add_entity(&mut self, line: &str) -> bool {
+        if let Some(obstacle) = Obstacle::parse(line) {
+            self.obstacles.push(obstacle);
+        } else if let Some(field) = ForceField::parse(line) {
+            self.fields.push(field);
+        } else if let Some(shape) = Shape::parse(line) {
+            self.shapes.push(shape);
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    /// Error for an invalid entity definition at line number `line_no`
+    fn invalid_entity(line: &str, line_no: usize) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid map entity on line {}: {}", line_no, line),
+        )
+    }
 }
 
 impl World {
+    /// Create a world from the map file `MAP_FILE_PATH`,
+    /// and the scene file `SCENE_FILE_PATH` if set
     pub fn new() -> io::Result<World> {
-        let (grid_size, walls) = World::load_map_data()?;
-        let block_size = SCREEN_DIMENSIONS as f32 / (grid_size as f32);
-        Ok(World {
+        let mut world = World::load(MAP_FILE_PATH)?;
+        if let Some(path) = SCENE_FILE_PATH {
+            world.load_scene(path)?;
+        }
+
+        Ok(world)
+    }
+
+    /// Create a world from the map file located at `path`
+    ///
+    /// If the map doesn't define a spawn or target block,
+    /// `ROCKET_SPAWN_LOCATION` and `TARGET_LOCATION` are used instead
+    pub fn load(path: &str) -> io::Result<World> {
+        let mut world = World::read_map(BufReader::new(File::open(path)?))?;
+        world.path = Some(path.to_string());
+        Ok(world)
+    }
+
+    /// Create a world from the contents of a map file, refer `World::load`
+    pub fn from_map_str(map: &str) -> io::Result<World> {
+        World::read_map(map.as_bytes())
+    }
+
+    fn read_map<R: BufRead>(reader: R) -> io::Result<World> {
+        let mut map = World::load_map_data(reader)?;
+        let mut world = World::from_walls(map.grid_size, map.walls);
+
+        if let Some(block) = map.spawn {
+            world.spawn = world.grid_to_window(block);
+        }
+        if let Some(block) = map.target {
+            world.target = world.grid_to_window(block);
+        }
+        map.checkpoints.sort_by_key(|&(letter, _)| letter);
+        world.checkpoints = map
+            .checkpoints
+            .iter()
+            .map(|&(_, block)| world.grid_to_window(block))
+            .collect();
+        world.map_entities = EntityCounts {
+            obstacles: map.obstacles.len(),
+            fields: map.fields.len(),
+            shapes: map.shapes.len(),
+        };
+        world.obstacles = map.obstacles;
+        world.fields = map.fields;
+        world.shapes = map.shapes;
+
+        Ok(world)
+    }
+
+    /// Add the shapes, obstacles and force fields of the scene file located at `path`
+    ///
+    /// A scene file has a definition per line, in the same format as the lines
+    /// after the grid of a map file, empty lines and lines starting with `#` are skipped
+    pub fn load_scene(&mut self, path: &str) -> io::Result<()> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut scene = MapData::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if !scene.add_entity(line) {
+                return Err(MapData::invalid_entity(line, i + 1));
+            }
+        }
+
+        self.add_entities(scene);
+        Ok(())
+    }
+
+    /// Add an obstacle, force field or shape, defined in the map file line format
+    /// Returns `false` if `line` isn't a valid definition
+    pub fn add_entity(&mut self, line: &str) -> bool {
+        let mut entities = MapData::default();
+        if !entities.add_entity(line) {
+            return false;
+        }
+
+        self.add_entities(entities);
+        true
+    }
+
+    fn add_entities(&mut self, entities: MapData) {
+        self.obstacles.extend(entities.obstacles);
+        self.fields.extend(entities.fields);
+        self.shapes.extend(entities.shapes);
+    }
+
+    /// Create a world of `grid_size` x `grid_size` blocks with walls at the grid positions `walls`
+    ///
+    /// The spawn and target are set to `ROCKET_SPAWN_LOCATION` and `TARGET_LOCATION`,
+    /// with no checkpoints, obstacles, force fields or shapes
+    pub fn from_walls(grid_size: usize, walls: Vec<(usize, usize)>) -> World {
+        World {
             walls,
             grid_size,
-            block_size,
-        })
+            block_size: SCREEN_DIMENSIONS as f32 / (grid_size as f32),
+            spawn: vec2(ROCKET_SPAWN_LOCATION.0, ROCKET_SPAWN_LOCATION.1),
+            target: vec2(TARGET_LOCATION.0, TARGET_LOCATION.1),
+            checkpoints: Vec::new(),
+            obstacles: Vec::new(),
+            fields: Vec::new(),
+            shapes: Vec::new(),
+            gravity: vec2(GRAVITY.0, GRAVITY.1),
+            path: None,
+            map_entities: EntityCounts::default(),
+        }
+    }
+
+    /// Map file the world was loaded from, `None` if it wasn't loaded from a file
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Write the world back to the map file it was loaded from, refer `World::save_as`
+    ///
+    /// Fails if the world wasn't loaded from a map file, e.g a generated world
+    pub fn save(&self) -> io::Result<()> {
+        match &self.path {
+            Some(path) => self.save_as(path),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The world wasn't loaded from a map file",
+            )),
+        }
+    }
+
+    /// Write the world to `path` in the map file format
+    ///
+    /// The blocks containing the spawn and target locations are written as `S` and `T`,
+    /// and the first 26 checkpoints as `a` to `z`
+    /// The obstacles, force fields and shapes of the map are written after the grid,
+    /// one per line, the ones from a scene file or added later aren't
+    ///
+    /// Fails if any of these blocks is a wall or holds another of them,
+    /// since the map format can't hold both
+    pub fn save_as(&self, path: &str) -> io::Result<()> {
+        let spawn = self.grid_position(&self.spawn);
+        let target = self.grid_position(&self.target);
+        let checkpoints: Vec<Option<(usize, usize)>> = self
+            .checkpoints
+            .iter()
+            .take(26)
+            .map(|c| self.grid_position(c))
+            .collect();
+
+        let markers: Vec<(usize, usize)> = [spawn, target]
+            .into_iter()
+            .chain(checkpoints.iter().cloned())
+            .flatten()
+            .collect();
+        if let Some(&block) = markers.iter().find(|&&block| self.is_wall(block)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "The spawn, target or a checkpoint is on the wall at {:?}",
+                    block
+                ),
+            ));
+        }
+        for (i, block) in markers.iter().enumerate() {
+            if markers[..i].contains(block) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "The spawn, target or checkpoints share the block at {:?}",
+                        block
+                    ),
+                ));
+            }
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        for i in 0..self.grid_size {
+            let line: String = (0..self.grid_size)
+                .map(|j| {
+                    let block = Some((i, j));
+                    if block == spawn {
+                        'S'
+                    } else if block == target {
+                        'T'
+                    } else if let Some(idx) = checkpoints.iter().position(|&c| c == block) {
+                        (b'a' + idx as u8) as char
+                    } else if self.is_wall((i, j)) {
+                        '0'
+                    } else {
+                        '1'
+                    }
+                })
+                .collect();
+            writeln!(writer, "{}", line)?;
+        }
+        let counts = self.map_entities;
+        for obstacle in &self.obstacles[..counts.obstacles] {
+            writeln!(writer, "{}", obstacle)?;
+        }
+        for field in &self.fields[..counts.fields] {
+            writeln!(writer, "{}", field)?;
+        }
+        for shape in &self.shapes[..counts.shapes] {
+            writeln!(writer, "{}", shape)?;
+        }
+
+        writer.flush()
     }
 
     /// Render the world
     pub fn draw(&self, draw: &Draw) {
-        let half_screen = (SCREEN_DIMENSIONS as f32) * 0.5;
-        let half_block_size = self.block_size * 0.5;
-
         // Draw the wall blocks
         // This is called on every simulation draw cycle
         // and hence not scalable for large grids
         for i in 0..self.grid_size {
             for j in 0..self.grid_size {
-                if self.is_wall((i, j)) != true {
+                if !self.is_wall((i, j)) {
                     continue;
                 }
 
-                let x = -half_screen + (self.block_size * j as f32);
-                let y = half_screen - (self.block_size * i as f32);
+                let pos = self.grid_to_window((i, j));
                 draw.rect()
-                    .x_y(x + half_block_size, y - half_block_size)
+                    .x_y(pos.x, pos.y)
                     .w_h(self.block_size, self.block_size)
                     .color(GRID_COLOR);
             }
         }
 
-        // Draw the target
+        self.shapes.iter().for_each(|s| s.draw(draw));
+        field::draw_arrows(&self.fields, draw);
+
+        // Draw the checkpoints as rings, and the target
+        for checkpoint in &self.checkpoints {
+            draw.ellipse()
+                .x_y(checkpoint.x, checkpoint.y)
+                .w_h(TARGET_RADIUS, TARGET_RADIUS)
+                .no_fill()
+                .stroke(CHECKPOINT_COLOR)
+                .stroke_weight(2.0);
+        }
         draw.ellipse()
-            .x_y(TARGET_LOCATION.0, TARGET_LOCATION.1)
+            .x_y(self.target.x, self.target.y)
             .w_h(TARGET_RADIUS, TARGET_RADIUS)
             .color(TARGET_COLOR);
     }
 
+    /// Render the obstacles as they are at frame `frame_idx`
+    pub fn draw_obstacles(&self, draw: &Draw, frame_idx: usize) {
+        self.obstacles.iter().for_each(|o| o.draw(draw, frame_idx));
+    }
+
+    /// Render the grid lines and the spawn location,
+    /// used as an overlay while editing the world
+    pub fn draw_grid(&self, draw: &Draw) {
+        let half_screen = (SCREEN_DIMENSIONS as f32) * 0.5;
+
+        for i in 0..=self.grid_size {
+            let offset = -half_screen + (self.block_size * i as f32);
+            draw.line()
+                .start(pt2(offset, -half_screen))
+                .end(pt2(offset, half_screen))
+                .weight(0.5)
+                .color(EDITOR_GRID_COLOR);
+            draw.line()
+                .start(pt2(-half_screen, offset))
+                .end(pt2(half_screen, offset))
+                .weight(0.5)
+                .color(EDITOR_GRID_COLOR);
+        }
+
+        draw.ellipse()
+            .x_y(self.spawn.x, self.spawn.y)
+            .w_h(TARGET_RADIUS, TARGET_RADIUS)
+            .color(EDITOR_SPAWN_COLOR);
+    }
+
+    pub fn spawn(&self) -> Vec2 {
+        self.spawn
+    }
+
+    pub fn target(&self) -> Vec2 {
+        self.target
+    }
+
+    pub fn checkpoints(&self) -> &[Vec2] {
+        &self.checkpoints
+    }
+
+    /// Positions rockets have to reach in order, the checkpoints followed by the target
+    pub fn course(&self) -> Vec<Vec2> {
+        let mut course = self.checkpoints.clone();
+        course.push(self.target);
+        course
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+
+    pub fn add_obstacle(&mut self, obstacle: Obstacle) {
+        self.obstacles.push(obstacle);
+    }
+
+    pub fn fields(&self) -> &[ForceField] {
+        &self.fields
+    }
+
+    pub fn add_field(&mut self, field: ForceField) {
+        self.fields.push(field);
+    }
+
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
+    }
+
+    pub fn add_shape(&mut self, shape: Shape) {
+        self.shapes.push(shape);
+    }
+
+    /// Check if the movement from `from` to `to` touches any shape
+    pub fn hits_shape(&self, from: &Vec2, to: &Vec2) -> bool {
+        self.shapes.iter().any(|s| s.intersects(*from, *to))
+    }
+
+    /// Check if the movement from `from` to `to` touches a moving obstacle
+    /// as it is at frame `frame_idx`
+    pub fn hits_obstacle(&self, from: &Vec2, to: &Vec2, frame_idx: usize) -> bool {
+        self.obstacles
+            .iter()
+            .any(|o| o.intersects(*from, *to, frame_idx))
+    }
+
+    pub fn gravity(&self) -> Vec2 {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec2) {
+        self.gravity = gravity;
+    }
+
+    /// Sum of the forces of the force fields and gravity at the window position `pos`
+    pub fn force_at(&self, pos: &Vec2) -> Vec2 {
+        self.gravity + field::total_force(&self.fields, *pos)
+    }
+
+    pub fn set_spawn(&mut self, pos: Vec2) {
+        self.spawn = pos;
+    }
+
+    pub fn set_target(&mut self, pos: Vec2) {
+        self.target = pos;
+    }
+
+    pub fn set_checkpoints(&mut self, checkpoints: Vec<Vec2>) {
+        self.checkpoints = checkpoints;
+    }
+
+    /// Add or remove the wall at grid position `(x, y)`
+    pub fn set_wall(&mut self, (x, y): (usize, usize), is_wall: bool) {
+        if is_wall == self.is_wall((x, y)) {
+            return;
+        }
+
+        if is_wall {
+            self.walls.push((x, y));
+        } else {
+            self.walls.retain(|&block| block != (x, y));
+        }
+    }
+
     /// Map a window position `pos` to grid position `(x, y)`
-    fn window_to_grid(pos: &Vec2) -> (usize, usize) {
+    /// Here `x` is the row and `y` is the column of the block
+    ///
+    /// Returns `None` if `pos` lies outside the grid
+    pub fn grid_position(&self, pos: &Vec2) -> Option<(usize, usize)> {
+        let half_screen = (SCREEN_DIMENSIONS as f32) * 0.5;
+        let col = (pos.x + half_screen) / self.block_size;
+        let row = (half_screen - pos.y) / self.block_size;
+        if col < 0.0 || row < 0.0 {
+            return None;
+        }
+
+        let (row, col) = (row as usize, col as usize);
+        if row >= self.grid_size || col >= self.grid_size {
+            return None;
+        }
+
+        Some((row, col))
+    }
+
+    /// Map a window position `pos` to the grid position of the closest block
+    /// Same as `grid_position` for positions inside the grid
+    pub fn nearest_block(&self, pos: &Vec2) -> (usize, usize) {
         let half_screen = (SCREEN_DIMENSIONS as f32) * 0.5;
-        let x = pos.x - -half_screen;
-        let y = pos.y - half_screen;
-        let x = (x / 24.0).abs() as usize;
-        let y = (y / 24.0).abs() as usize;
+        let last = self.grid_size.saturating_sub(1) as f32;
+        let col = ((pos.x + half_screen) / self.block_size).clamp(0.0, last);
+        let row = ((half_screen - pos.y) / self.block_size).clamp(0.0, last);
 
-        (x, y)
+        (row as usize, col as usize)
     }
 
-    /// Parse the input grid file located at `MAP_FILE_PATH`
+    pub fn block_size(&self) -> f32 {
+        self.block_size
+    }
+
+    /// Number of rows/columns in the grid
+    pub fn grid_size(&self) -> usize {
+        self.grid_size
+    }
+
+    /// Map a grid position `(x, y)` to the window position of the block's center
+    pub fn grid_to_window(&self, (x, y): (usize, usize)) -> Vec2 {
+        let half_screen = (SCREEN_DIMENSIONS as f32) * 0.5;
+        let half_block_size = self.block_size * 0.5;
+        vec2(
+            -half_screen + (self.block_size * y as f32) + half_block_size,
+            half_screen - (self.block_size * x as f32) - half_block_size,
+        )
+    }
+
+    /// Parse the input grid file read from `reader`
     /// And create a vector of all wall positions
-    fn load_map_data() -> io::Result<(usize, Vec<(usize, usize)>)> {
+    ///
+    /// `0` is a wall, `1` is a no-wall block,
+    /// `S` and `T` are no-wall blocks marking the spawn and target locations,
+    /// `a` to `z` are no-wall blocks marking the checkpoints, passed in alphabetical order
+    /// Lines after the grid define obstacles, force fields and shapes,
+    /// refer `Obstacle`, `ForceField` and `Shape`
+    fn load_map_data<R: BufRead>(reader: R) -> io::Result<MapData> {
         // IO errors propagated to caller
         // TODO: handle what happens with the map file isn't a square
-        let file = File::open(MAP_FILE_PATH)?;
-        let reader = BufReader::new(file);
-        let mut grid = Vec::new();
-        let mut grid_size = 0;
+        let mut map = MapData::default();
 
-        for (i, line) in reader.lines().into_iter().enumerate() {
+        for (i, line) in reader.lines().enumerate() {
             if let Ok(line) = line {
+                // Grid rows have no whitespace, anything else is an entity definition
+                // Trailing spaces and carriage returns (CRLF) don't count
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                if line.contains(char::is_whitespace) {
+                    if !map.add_entity(line) {
+                        return Err(MapData::invalid_entity(line, i + 1));
+                    }
+                    continue;
+                }
+
                 // TODO is there a better way to calc grid matrix size?
-                grid_size = line.len();
+                map.grid_size = line.len();
                 for (j, c) in line.chars().enumerate() {
-                    if c == '1' {
-                        continue;
+                    match c {
+                        '0' => map.walls.push((i, j)),
+                        'S' => map.spawn = Some((i, j)),
+                        'T' => map.target = Some((i, j)),
+                        'a'..='z' => map.checkpoints.push((c, (i, j))),
+                        _ => continue,
                     }
-
-                    grid.push((i, j));
                 }
             }
         }
 
-        Ok((grid_size, grid))
+        Ok(map)
     }
 }
 
@@ -120,7 +590,12 @@ impl Wall<(usize, usize)> for World {
 }
 
 impl Wall<&Vec2> for World {
-    /// Check if a wall exists in a given world position `pos`
+    /// Check if a wall or an obstacle exists in a given world position `pos` at `frame_idx`
+    fn is_wall_at(&self, pos: &Vec2, frame_idx: usize) -> bool {
+        self.is_wall(pos) || self.obstacles.iter().any(|o| o.contains(*pos, frame_idx))
+    }
+
+    /// Check if a wall exists in a given world position `pos`, inside a grid wall or a shape
     fn is_wall(&self, pos: &Vec2) -> bool {
         let half_screen = (SCREEN_DIMENSIONS as f32) * 0.5;
 
@@ -131,8 +606,137 @@ impl Wall<&Vec2> for World {
             return true;
         }
 
+        if self.shapes.iter().any(|s| s.contains(*pos)) {
+            return true;
+        }
+
         // Actual walls from map matrix
-        let (x, y) = World::window_to_grid(&pos);
-        self.is_wall((y, x)) == true
+        match self.grid_position(pos) {
+            Some(block) => self.is_wall(block),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_rows_with_trailing_whitespace_load() {
+        let world =
+            World::from_map_str("S10 \r\n101\t\r\n11T\r\nwind 0,0 10,10 0.1,0\r\n").unwrap();
+        assert_eq!(world.grid_size(), 3);
+        assert!(world.is_wall((0, 2)) && world.is_wall((1, 1)));
+        assert_eq!(world.fields().len(), 1);
+    }
+
+    /// Path of a file in the temporary directory, unique to the running test
+    fn temp_path(name: &str) -> String {
+        let file = format!("genetic-rockets-{}-{}", std::process::id(), name);
+        std::env::temp_dir()
+            .join(file)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    const MAP: &str = "\
+S1110
+10b10
+1a100
+1001T
+patrol 10 2 -50,0 50,0
+vortex 0,0 100 0.05
+circle 100,100 30
+";
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let world = World::from_map_str(MAP).unwrap();
+        let path = temp_path("round-trip.txt");
+        world.save_as(&path).unwrap();
+        let loaded = World::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.grid_size(), world.grid_size());
+        for i in 0..world.grid_size() {
+            for j in 0..world.grid_size() {
+                assert_eq!(
+                    loaded.is_wall((i, j)),
+                    world.is_wall((i, j)),
+                    "block {:?}",
+                    (i, j)
+                );
+            }
+        }
+        assert_eq!(loaded.spawn(), world.spawn());
+        assert_eq!(loaded.target(), world.target());
+        assert_eq!(loaded.checkpoints(), world.checkpoints());
+        assert_eq!(world.checkpoints().len(), 2);
+        assert_eq!(loaded.obstacles(), world.obstacles());
+        assert_eq!(loaded.fields(), world.fields());
+        assert_eq!(loaded.shapes(), world.shapes());
+    }
+
+    #[test]
+    fn save_rejects_spawn_on_a_wall() {
+        let mut world = World::from_map_str(MAP).unwrap();
+        world.set_spawn(world.grid_to_window((0, 4)));
+        assert!(world.is_wall((0, 4)));
+        let path = temp_path("spawn-on-wall.txt");
+        assert_eq!(
+            world.save_as(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn save_writes_only_the_map_back_to_its_file() {
+        let path = temp_path("source.txt");
+        let scene = temp_path("scene.txt");
+        std::fs::write(&path, MAP).unwrap();
+        std::fs::write(&scene, "segment 0,0 10,10\nwind 0,0 10,10 0.1,0\n").unwrap();
+        let mut world = World::load(&path).unwrap();
+        world.load_scene(&scene).unwrap();
+        assert!(world.add_entity("circle -100,-100 20"));
+        world.set_wall((0, 1), true);
+        world.save().unwrap();
+
+        let loaded = World::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&scene).unwrap();
+        assert!(loaded.is_wall((0, 1)));
+        assert_eq!(loaded.obstacles().len(), 1);
+        assert_eq!(loaded.fields().len(), 1);
+        assert_eq!(loaded.shapes().len(), 1);
+        assert_eq!(loaded.shapes(), &world.shapes()[..1]);
+        assert_eq!(loaded.path(), Some(path.as_str()));
+    }
+
+    #[test]
+    fn save_rejects_worlds_not_loaded_from_a_file() {
+        let world = World::from_map_str(MAP).unwrap();
+        assert_eq!(world.path(), None);
+        assert_eq!(
+            world.save().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn save_rejects_spawn_and_target_in_the_same_block() {
+        let mut world = World::from_map_str(MAP).unwrap();
+        world.set_target(world.spawn() + vec2(1.0, -1.0));
+        assert_eq!(
+            world.grid_position(&world.spawn()),
+            world.grid_position(&world.target())
+        );
+        let path = temp_path("spawn-on-target.txt");
+        assert_eq!(
+            world.save_as(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert!(!std::path::Path::new(&path).exists());
     }
 }